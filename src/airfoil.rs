@@ -0,0 +1,396 @@
+//! NACA 4- and 5-digit airfoil sections, rasterized through `Shape`.
+
+use crate::shape::Shape;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Points along each surface of the generated outline.
+const SURFACE_POINTS: usize = 80;
+
+/// Camber line of a NACA section, with coordinates as fractions of the chord.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NacaProfile {
+    /// `MPXX`: maximum camber `camber` at `camber_position` along the chord.
+    FourDigit {
+        camber: f64,
+        camber_position: f64,
+        thickness: f64,
+    },
+    /// `LPSTT`: camber designed for `lift_coefficient` with its peak at
+    /// `camber_position`, optionally with a reflexed trailing edge.
+    FiveDigit {
+        lift_coefficient: f64,
+        camber_position: f64,
+        reflex: bool,
+        thickness: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseNacaError {
+    /// Neither four nor five ASCII digits.
+    Format(String),
+    /// Digits that do not name a profile, e.g. camber without a position.
+    Unsupported(String),
+}
+
+impl Display for ParseNacaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseNacaError::Format(code) => {
+                write!(f, "'{}' is not a 4- or 5-digit NACA code", code)
+            }
+            ParseNacaError::Unsupported(code) => {
+                write!(f, "NACA {} is not a supported profile", code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseNacaError {}
+
+impl FromStr for NacaProfile {
+    type Err = ParseNacaError;
+
+    /// Parses a code such as `"2412"` or `"23012"`, with or without a leading
+    /// `"NACA"`.
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let trimmed = code.trim();
+        let digits = trimmed
+            .strip_prefix("NACA")
+            .or_else(|| trimmed.strip_prefix("naca"))
+            .unwrap_or(trimmed)
+            .trim();
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseNacaError::Format(code.to_string()));
+        }
+        let digit = |i: usize| digits.as_bytes()[i] - b'0';
+        let thickness = |from: usize| digits[from..].parse::<f64>().unwrap() / 100.0;
+        let unsupported = || Err(ParseNacaError::Unsupported(digits.to_string()));
+        let profile = match digits.len() {
+            4 => NacaProfile::FourDigit {
+                camber: digit(0) as f64 / 100.0,
+                camber_position: digit(1) as f64 / 10.0,
+                thickness: thickness(2),
+            },
+            5 => NacaProfile::FiveDigit {
+                lift_coefficient: digit(0) as f64 * 3.0 / 20.0,
+                camber_position: digit(1) as f64 / 20.0,
+                reflex: match digit(2) {
+                    0 => false,
+                    1 => true,
+                    _ => return unsupported(),
+                },
+                thickness: thickness(3),
+            },
+            _ => return Err(ParseNacaError::Format(code.to_string())),
+        };
+        let valid = match profile {
+            NacaProfile::FourDigit {
+                camber,
+                camber_position,
+                thickness,
+            } => (camber == 0.0) == (camber_position == 0.0) && thickness > 0.0,
+            NacaProfile::FiveDigit {
+                camber_position,
+                reflex,
+                thickness,
+                ..
+            } => five_digit_constants(camber_position, reflex).is_some() && thickness > 0.0,
+        };
+        if valid { Ok(profile) } else { unsupported() }
+    }
+}
+
+/// `(m, k1, k2 / k1)` of the tabulated 5-digit camber lines, designed for a
+/// lift coefficient of 0.3.
+// 0.3180 is a tabulated value, not an approximation of 1 / pi.
+#[allow(clippy::approx_constant)]
+fn five_digit_constants(camber_position: f64, reflex: bool) -> Option<(f64, f64, f64)> {
+    let index = (camber_position * 20.0).round() as usize;
+    if reflex {
+        match index {
+            2 => Some((0.1300, 51.99, 0.000764)),
+            3 => Some((0.2170, 15.793, 0.00677)),
+            4 => Some((0.3180, 6.520, 0.0303)),
+            5 => Some((0.4410, 3.191, 0.1355)),
+            _ => None,
+        }
+    } else {
+        match index {
+            1 => Some((0.0580, 361.4, 0.0)),
+            2 => Some((0.1260, 51.64, 0.0)),
+            3 => Some((0.2025, 15.957, 0.0)),
+            4 => Some((0.2900, 6.643, 0.0)),
+            5 => Some((0.3910, 3.230, 0.0)),
+            _ => None,
+        }
+    }
+}
+
+impl NacaProfile {
+    fn thickness(&self) -> f64 {
+        match *self {
+            NacaProfile::FourDigit { thickness, .. } | NacaProfile::FiveDigit { thickness, .. } => {
+                thickness
+            }
+        }
+    }
+
+    /// Half thickness at chord fraction `x`, closed at the trailing edge.
+    fn half_thickness(&self, x: f64) -> f64 {
+        5.0 * self.thickness()
+            * (0.2969 * x.sqrt() - 0.1260 * x - 0.3516 * x.powi(2) + 0.2843 * x.powi(3)
+                - 0.1036 * x.powi(4))
+    }
+
+    /// Height of the camber line and its slope at chord fraction `x`.
+    fn camber(&self, x: f64) -> (f64, f64) {
+        match *self {
+            NacaProfile::FourDigit {
+                camber: m,
+                camber_position: p,
+                ..
+            } => {
+                if m == 0.0 {
+                    (0.0, 0.0)
+                } else if x < p {
+                    (
+                        m / (p * p) * (2.0 * p * x - x * x),
+                        2.0 * m / (p * p) * (p - x),
+                    )
+                } else {
+                    (
+                        m / (1.0 - p).powi(2) * (1.0 - 2.0 * p + 2.0 * p * x - x * x),
+                        2.0 * m / (1.0 - p).powi(2) * (p - x),
+                    )
+                }
+            }
+            NacaProfile::FiveDigit {
+                lift_coefficient,
+                camber_position,
+                reflex,
+                ..
+            } => {
+                let Some((m, k1, k2)) = five_digit_constants(camber_position, reflex) else {
+                    return (0.0, 0.0);
+                };
+                let scale = lift_coefficient / 0.3 * k1 / 6.0;
+                let (y, slope) = if !reflex {
+                    if x < m {
+                        (
+                            x.powi(3) - 3.0 * m * x * x + m * m * (3.0 - m) * x,
+                            3.0 * x * x - 6.0 * m * x + m * m * (3.0 - m),
+                        )
+                    } else {
+                        (m.powi(3) * (1.0 - x), -m.powi(3))
+                    }
+                } else {
+                    let tail = k2 * (1.0 - m).powi(3);
+                    if x < m {
+                        (
+                            (x - m).powi(3) - tail * x - m.powi(3) * x + m.powi(3),
+                            3.0 * (x - m).powi(2) - tail - m.powi(3),
+                        )
+                    } else {
+                        (
+                            k2 * (x - m).powi(3) - tail * x - m.powi(3) * x + m.powi(3),
+                            3.0 * k2 * (x - m).powi(2) - tail - m.powi(3),
+                        )
+                    }
+                };
+                (scale * y, scale * slope)
+            }
+        }
+    }
+}
+
+/// A NACA section placed in the domain, in the cell units of `Shape`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Airfoil {
+    pub profile: NacaProfile,
+    pub chord: f64,
+    /// Quarter-chord point, which the section is rotated about.
+    pub position: (f64, f64),
+    /// Angle of attack in radians; positive values raise the leading edge
+    /// towards `y = 0`.
+    pub angle_of_attack: f64,
+}
+
+impl Airfoil {
+    pub fn new(profile: NacaProfile, chord: f64, position: (f64, f64)) -> Self {
+        Airfoil {
+            profile,
+            chord,
+            position,
+            angle_of_attack: 0.0,
+        }
+    }
+
+    pub fn with_angle_of_attack(mut self, angle_of_attack: f64) -> Self {
+        self.angle_of_attack = angle_of_attack;
+        self
+    }
+
+    /// Outline of the section as a polygon, running from the trailing edge
+    /// over the upper surface to the leading edge and back along the lower
+    /// one, with points clustered at both edges.
+    pub fn shape(&self) -> Shape {
+        let (sin, cos) = self.angle_of_attack.sin_cos();
+        let place = |x: f64, y: f64| {
+            // Chord fractions to cells relative to the quarter chord, with
+            // the upper surface towards y = 0.
+            let (dx, dy) = ((x - 0.25) * self.chord, -y * self.chord);
+            (
+                self.position.0 + dx * cos - dy * sin,
+                self.position.1 + dx * sin + dy * cos,
+            )
+        };
+        let stations: Vec<f64> = (0..=SURFACE_POINTS)
+            .map(|i| {
+                let beta = std::f64::consts::PI * i as f64 / SURFACE_POINTS as f64;
+                0.5 * (1.0 - beta.cos())
+            })
+            .collect();
+
+        // `side` is 1 on the upper surface and -1 on the lower one.
+        let surface = |x: f64, side: f64| {
+            let (yc, slope) = self.profile.camber(x);
+            let (sin, cos) = slope.atan().sin_cos();
+            let yt = side * self.profile.half_thickness(x);
+            place(x - yt * sin, yc + yt * cos)
+        };
+        let upper = stations.iter().rev().map(|&x| surface(x, 1.0));
+        let lower = stations[1..SURFACE_POINTS]
+            .iter()
+            .map(|&x| surface(x, -1.0));
+        let points = upper.chain(lower).collect();
+        Shape::polygon(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Airfoil, NacaProfile, ParseNacaError};
+
+    /// Largest value of `f` over the chord and the chord fraction it occurs at.
+    fn peak(f: impl Fn(f64) -> f64) -> (f64, f64) {
+        (0..=10000)
+            .map(|i| i as f64 / 10000.0)
+            .map(|x| (f(x), x))
+            .fold(
+                (f64::MIN, 0.0),
+                |best, next| if next.0 > best.0 { next } else { best },
+            )
+    }
+
+    #[test]
+    fn parses_four_and_five_digit_codes() {
+        assert_eq!(
+            "NACA 2412".parse(),
+            Ok(NacaProfile::FourDigit {
+                camber: 0.02,
+                camber_position: 0.4,
+                thickness: 0.12,
+            })
+        );
+        assert_eq!(
+            "23012".parse(),
+            Ok(NacaProfile::FiveDigit {
+                lift_coefficient: 0.3,
+                camber_position: 0.15,
+                reflex: false,
+                thickness: 0.12,
+            })
+        );
+        assert!(matches!(
+            "naca23112".parse(),
+            Ok(NacaProfile::FiveDigit { reflex: true, .. })
+        ));
+        for code in ["", "24", "241", "241200", "24a2", "NACA-2412"] {
+            assert!(
+                matches!(code.parse::<NacaProfile>(), Err(ParseNacaError::Format(_))),
+                "{:?}",
+                code
+            );
+        }
+        for code in ["2012", "0412", "2400", "23212", "20012", "21112", "26012"] {
+            assert!(
+                matches!(
+                    code.parse::<NacaProfile>(),
+                    Err(ParseNacaError::Unsupported(_))
+                ),
+                "{:?}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn thickness_peaks_near_thirty_percent_chord() {
+        let profile: NacaProfile = "0012".parse().unwrap();
+        let (half, at) = peak(|x| profile.half_thickness(x));
+        assert!((2.0 * half - 0.12).abs() < 1e-3, "{}", 2.0 * half);
+        assert!((at - 0.3).abs() < 0.01, "{}", at);
+        assert_eq!(profile.half_thickness(0.0), 0.0);
+        assert!(profile.half_thickness(1.0).abs() < 1e-4);
+        assert_eq!(profile.camber(0.5), (0.0, 0.0));
+    }
+
+    #[test]
+    fn four_digit_camber_peaks_at_its_position() {
+        let profile: NacaProfile = "4412".parse().unwrap();
+        let (camber, at) = peak(|x| profile.camber(x).0);
+        assert!((camber - 0.04).abs() < 1e-9);
+        assert!((at - 0.4).abs() < 1e-9);
+        assert_smooth(profile, 0.4);
+    }
+
+    #[test]
+    fn five_digit_camber_matches_tabulated_sections() {
+        let profile: NacaProfile = "23012".parse().unwrap();
+        let (camber, at) = peak(|x| profile.camber(x).0);
+        assert!((camber - 0.0184).abs() < 2e-4, "{}", camber);
+        assert!((at - 0.15).abs() < 0.01, "{}", at);
+        assert_smooth(profile, 0.2025);
+
+        let reflexed: NacaProfile = "23112".parse().unwrap();
+        assert!(reflexed.camber(0.0).0.abs() < 1e-9);
+        assert!(reflexed.camber(1.0).0.abs() < 1e-6);
+        assert_smooth(reflexed, 0.2170);
+    }
+
+    /// The two branches of the camber line meet with matching height and
+    /// slope at `joint`.
+    fn assert_smooth(profile: NacaProfile, joint: f64) {
+        let (before, after) = (profile.camber(joint - 1e-9), profile.camber(joint));
+        assert!((before.0 - after.0).abs() < 1e-8, "{:?}", profile);
+        assert!((before.1 - after.1).abs() < 1e-6, "{:?}", profile);
+    }
+
+    #[test]
+    fn shape_is_placed_by_its_quarter_chord() {
+        let profile: NacaProfile = "0012".parse().unwrap();
+        let level = Airfoil::new(profile, 100.0, (60.0, 50.0)).shape();
+        // Leading edge at x = 35 and trailing edge at x = 135.
+        assert!(level.contains(36.0, 50.0) && level.contains(134.0, 50.0));
+        assert!(!level.contains(34.0, 50.0) && !level.contains(136.0, 50.0));
+        for fraction in [0.1, 0.3, 0.65] {
+            let (x, dy) = (
+                35.0 + 100.0 * fraction,
+                100.0 * profile.half_thickness(fraction),
+            );
+            assert!(level.contains(x, 50.0 - dy + 0.1) && level.contains(x, 50.0 + dy - 0.1));
+            assert!(!level.contains(x, 50.0 - dy - 0.1) && !level.contains(x, 50.0 + dy + 0.1));
+        }
+
+        let pitched = Airfoil::new(profile, 100.0, (60.0, 50.0))
+            .with_angle_of_attack(20f64.to_radians())
+            .shape();
+        let (sin, cos) = 20f64.to_radians().sin_cos();
+        // Positive angles raise the leading edge towards y = 0.
+        assert!(pitched.contains(60.0 - 24.0 * cos, 50.0 - 24.0 * sin));
+        assert!(pitched.contains(60.0 + 74.0 * cos, 50.0 + 74.0 * sin));
+        assert!(!pitched.contains(36.0, 50.0));
+    }
+}
@@ -0,0 +1,811 @@
+use std::fmt::{self, Display};
+
+/// Physical and numerical parameters read by every step of a `Simulation`.
+///
+/// `SimConfig::default()` reproduces the original wind tunnel setup; use
+/// `SimConfig::builder()` to change individual knobs with validation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct SimConfig {
+    pub overrelaxation: f64,
+    pub projection_iterations: u32,
+    pub gravity: f64,
+    pub density: f64,
+    pub windspeed: f64,
+    /// Simulated time covered by one call to `Simulation::step`.
+    pub dt: f64,
+    pub h: f64,
+    /// Rows each smoke band covers on either side of its centre row,
+    /// including it; at least 1 while there are bands.
+    pub band_width: usize,
+    /// Smoke bands fed in along the left edge; zero disables them.
+    pub num_bands: usize,
+    pub with_gravity: bool,
+    /// Constant acceleration `(x, y)` added to every open face each step, for
+    /// driving periodic channels. Positive `y` points down, as for `gravity`.
+    pub body_force: (f64, f64),
+    pub draw_obstacle: bool,
+    /// Draws obstacles with fractional coverage: faces keep the fraction of
+    /// their length outside the solid and the projection weights them by it,
+    /// instead of staircasing every boundary at grid resolution.
+    pub cut_cells: bool,
+    pub pressure_solver: PressureSolver,
+    /// Divergence, measured with `divergence_norm`, below which an iterative
+    /// solver stops.
+    pub solver_tolerance: f64,
+    /// Iteration cap for solvers that run to `solver_tolerance`.
+    pub max_solver_iterations: u32,
+    pub divergence_norm: DivergenceNorm,
+    /// Lets SOR stop before `projection_iterations` sweeps once the divergence
+    /// is below `solver_tolerance`.
+    pub sor_until_converged: bool,
+    pub sor_ordering: SorOrdering,
+    pub advection: AdvectionScheme,
+    pub backtrace: Backtrace,
+    pub interpolation: Interpolation,
+    /// Strength of the vorticity confinement force; zero disables it.
+    pub vorticity_confinement: f64,
+    /// Kinematic viscosity; zero leaves only numerical diffusion.
+    pub viscosity: f64,
+    pub diffusion: Diffusion,
+    /// Jacobi iterations per implicit diffusion solve.
+    pub diffusion_iterations: u32,
+    /// Temperature the fluid starts at and buoyancy is measured against.
+    pub ambient_temperature: f64,
+    /// Upward acceleration per degree above `ambient_temperature`.
+    pub thermal_expansion: f64,
+    /// Downward acceleration per unit of smoke density.
+    pub smoke_weight: f64,
+    /// Diffusivity of the temperature field; zero leaves it purely advected.
+    pub thermal_diffusivity: f64,
+    pub boundaries: Boundaries,
+    /// How the speed of `Inflow` edges varies across the edge.
+    pub inflow_shape: InflowShape,
+    /// How the speed of `Inflow` edges varies over time.
+    pub inflow_modulation: InflowModulation,
+    pub timestep: Timestep,
+}
+
+/// Condition applied on one edge of the domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Boundary {
+    /// Solid wall; tangential velocity is reflected to zero at the wall.
+    NoSlip,
+    /// Solid wall the flow may slide along.
+    FreeSlip,
+    /// Normal velocity held at `windspeed`, pointing along +x or +y and
+    /// shaped by `inflow_shape` and `inflow_modulation`.
+    Inflow,
+    /// Zero-gradient outflow: the normal velocity is copied from the first
+    /// interior face, then shifted so the domain conserves mass.
+    Outflow,
+    /// Convective outflow: the normal velocity is carried out through the
+    /// edge at the mean outflow speed, so eddies leave without reflecting.
+    /// Shifted like `Outflow` to conserve mass.
+    Convective,
+    /// Open edge held at zero pressure; the projection decides how much flow
+    /// leaves or enters through it.
+    PressureOutlet,
+    /// Flow leaving this edge re-enters through the opposite one, which must
+    /// also be periodic. Red-black SOR converges fastest with an even number
+    /// of cells along a periodic axis.
+    Periodic,
+}
+
+/// Boundary condition of each domain edge. `top` is the `y = 0` row, which
+/// the viewer draws at the top of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct Boundaries {
+    pub left: Boundary,
+    pub right: Boundary,
+    pub top: Boundary,
+    pub bottom: Boundary,
+}
+
+impl Boundaries {
+    /// Whether the domain wraps around in x and in y.
+    pub fn periodic(&self) -> (bool, bool) {
+        (
+            self.left == Boundary::Periodic && self.right == Boundary::Periodic,
+            self.top == Boundary::Periodic && self.bottom == Boundary::Periodic,
+        )
+    }
+
+    /// The same condition on all four edges.
+    pub fn all(boundary: Boundary) -> Self {
+        Boundaries {
+            left: boundary,
+            right: boundary,
+            top: boundary,
+            bottom: boundary,
+        }
+    }
+}
+
+impl Default for Boundaries {
+    /// The wind tunnel: wind blowing in from the left and leaving through a
+    /// convective outflow on the right, with free-slip walls above and below.
+    fn default() -> Self {
+        Boundaries {
+            left: Boundary::Inflow,
+            right: Boundary::Convective,
+            top: Boundary::FreeSlip,
+            bottom: Boundary::FreeSlip,
+        }
+    }
+}
+
+/// Speed across an `Inflow` edge as a multiple of `windspeed`. Positions run
+/// from 0 at the start of the edge (its left or top end) to 1 at the other.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum InflowShape {
+    Uniform,
+    /// Fully developed channel flow, peaking at `windspeed` mid-edge and
+    /// falling to zero at both ends.
+    Parabolic,
+    /// Polynomial boundary layer of the given thickness, in simulation
+    /// units, growing from zero at both ends of the edge.
+    BoundaryLayer {
+        thickness: f64,
+    },
+    /// `(position, factor)` pairs with increasing positions, linearly
+    /// interpolated and held constant past either end.
+    Table(Vec<(f64, f64)>),
+}
+
+impl InflowShape {
+    /// Speed factor at `position` along an edge of `length` simulation units.
+    pub fn factor(&self, position: f64, length: f64) -> f64 {
+        match self {
+            InflowShape::Uniform => 1.0,
+            InflowShape::Parabolic => 4.0 * position * (1.0 - position),
+            InflowShape::BoundaryLayer { thickness } => {
+                let distance = position.min(1.0 - position) * length;
+                let eta = (distance / thickness).min(1.0);
+                2.0 * eta - 2.0 * eta.powi(3) + eta.powi(4)
+            }
+            InflowShape::Table(table) => interpolate(table, position),
+        }
+    }
+}
+
+/// Time dependence of the inflow speed, as a factor on `InflowShape`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum InflowModulation {
+    Steady,
+    /// `1 + amplitude * sin(2 pi t / period)`.
+    Pulsating {
+        amplitude: f64,
+        period: f64,
+    },
+    /// A single one-minus-cosine gust adding up to `amplitude` between
+    /// `start` and `start + duration`.
+    Gust {
+        start: f64,
+        duration: f64,
+        amplitude: f64,
+    },
+    /// `(time, factor)` pairs with increasing times, linearly interpolated
+    /// and held constant past either end.
+    Table(Vec<(f64, f64)>),
+}
+
+impl InflowModulation {
+    /// Speed factor at simulated time `time`.
+    pub fn factor(&self, time: f64) -> f64 {
+        match self {
+            InflowModulation::Steady => 1.0,
+            InflowModulation::Pulsating { amplitude, period } => {
+                1.0 + amplitude * (std::f64::consts::TAU * time / period).sin()
+            }
+            InflowModulation::Gust {
+                start,
+                duration,
+                amplitude,
+            } => {
+                let phase = (time - start) / duration;
+                if (0.0..=1.0).contains(&phase) {
+                    1.0 + amplitude * 0.5 * (1.0 - (std::f64::consts::TAU * phase).cos())
+                } else {
+                    1.0
+                }
+            }
+            InflowModulation::Table(table) => interpolate(table, time),
+        }
+    }
+}
+
+/// Piecewise-linear lookup in a table sorted by its first column.
+fn interpolate(table: &[(f64, f64)], x: f64) -> f64 {
+    let i = table.partition_point(|&(key, _)| key <= x);
+    if i == 0 {
+        return table[0].1;
+    }
+    if i == table.len() {
+        return table[i - 1].1;
+    }
+    let ((x0, y0), (x1, y1)) = (table[i - 1], table[i]);
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+/// Whether a lookup table is non-empty, finite and strictly increasing in
+/// its first column.
+fn valid_table(table: &[(f64, f64)]) -> bool {
+    !table.is_empty()
+        && table.iter().all(|(x, y)| x.is_finite() && y.is_finite())
+        && table.windows(2).all(|pair| pair[0].0 < pair[1].0)
+}
+
+/// How `Simulation::step` covers its `dt`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Timestep {
+    /// One step of exactly `dt`.
+    Fixed,
+    /// Splits `dt` into equal substeps short enough that the fastest
+    /// velocity component crosses at most `cfl` cells per substep, using no
+    /// more than `max_substeps`.
+    Adaptive { cfl: f64, max_substeps: u32 },
+}
+
+/// How diffusion steps are integrated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Diffusion {
+    /// Explicit while it is stable (`k * dt / h^2 <= 0.2`), implicit beyond.
+    Auto,
+    Explicit,
+    /// Backward Euler, solved with `diffusion_iterations` Jacobi sweeps.
+    Implicit,
+}
+
+/// Reconstruction used when sampling velocity and smoke between grid points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Interpolation {
+    Bilinear,
+    /// Catmull-Rom cubic, clamped to the four nearest samples.
+    CatmullRom,
+    /// Cubic Hermite with Fritsch-Carlson slope limiting; never overshoots.
+    MonotoneCubic,
+}
+
+/// Integrator used to trace sample points back along the velocity field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Backtrace {
+    /// One explicit Euler step using the velocity at the sample point.
+    Euler,
+    /// Midpoint method.
+    Rk2,
+    /// Ralston's third-order method.
+    Rk3,
+}
+
+/// Scheme used to move velocity and smoke along the flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AdvectionScheme {
+    /// Single backtrace with bilinear sampling; cheap but diffusive.
+    SemiLagrangian,
+    /// Forward and backward semi-Lagrangian passes combined to cancel the
+    /// leading error term, clamped to the values around the backtrace.
+    MacCormack,
+    /// Back and forth error compensation and correction: the error estimate
+    /// is subtracted before a final semi-Lagrangian pass, then clamped.
+    Bfecc,
+}
+
+/// Order in which SOR visits cells. Red-black relaxes the two checkerboard
+/// colors in turn, which lets each half-sweep run in parallel with results
+/// that do not depend on the number of threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SorOrdering {
+    Lexicographic,
+    RedBlack,
+}
+
+/// How the per-cell divergence is reduced to one number for convergence tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DivergenceNorm {
+    Max,
+    Rms,
+}
+
+/// Method used to make the velocity field divergence free each step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PressureSolver {
+    /// Gauss-Seidel with overrelaxation for exactly `projection_iterations` sweeps.
+    Sor,
+    /// Conjugate gradient preconditioned with modified incomplete Cholesky,
+    /// run until the divergence drops below `solver_tolerance`.
+    ConjugateGradient,
+    /// Geometric multigrid cycles run until the divergence drops below
+    /// `solver_tolerance`; scales to much larger grids than SOR.
+    Multigrid(MultigridCycle),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MultigridCycle {
+    V,
+    F,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            overrelaxation: 1.94,
+            projection_iterations: 100,
+            gravity: 7.2,
+            density: 10.0,
+            windspeed: 10.0,
+            dt: 0.22,
+            h: 0.4,
+            band_width: 5,
+            num_bands: 9,
+            with_gravity: false,
+            body_force: (0.0, 0.0),
+            draw_obstacle: true,
+            cut_cells: false,
+            pressure_solver: PressureSolver::Sor,
+            solver_tolerance: 1e-6,
+            max_solver_iterations: 1000,
+            divergence_norm: DivergenceNorm::Max,
+            sor_until_converged: false,
+            sor_ordering: SorOrdering::Lexicographic,
+            advection: AdvectionScheme::SemiLagrangian,
+            backtrace: Backtrace::Euler,
+            interpolation: Interpolation::Bilinear,
+            vorticity_confinement: 0.0,
+            viscosity: 0.0,
+            diffusion: Diffusion::Auto,
+            diffusion_iterations: 40,
+            ambient_temperature: 0.0,
+            thermal_expansion: 0.0,
+            smoke_weight: 0.0,
+            thermal_diffusivity: 0.0,
+            boundaries: Boundaries::default(),
+            inflow_shape: InflowShape::Uniform,
+            inflow_modulation: InflowModulation::Steady,
+            timestep: Timestep::Fixed,
+        }
+    }
+}
+
+impl SimConfig {
+    pub fn builder() -> SimConfigBuilder {
+        SimConfigBuilder {
+            config: SimConfig::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(self.overrelaxation > 0.0 && self.overrelaxation < 2.0) {
+            return Err(ConfigError::OutOfRange {
+                field: "overrelaxation",
+                value: self.overrelaxation,
+                expected: "in (0, 2)",
+            });
+        }
+        if self.projection_iterations == 0 {
+            return Err(ConfigError::OutOfRange {
+                field: "projection_iterations",
+                value: 0.0,
+                expected: "at least 1",
+            });
+        }
+        if self.max_solver_iterations == 0 {
+            return Err(ConfigError::OutOfRange {
+                field: "max_solver_iterations",
+                value: 0.0,
+                expected: "at least 1",
+            });
+        }
+        if self.diffusion_iterations == 0 {
+            return Err(ConfigError::OutOfRange {
+                field: "diffusion_iterations",
+                value: 0.0,
+                expected: "at least 1",
+            });
+        }
+        if self.num_bands > 0 && self.band_width == 0 {
+            return Err(ConfigError::OutOfRange {
+                field: "band_width",
+                value: 0.0,
+                expected: "at least 1 when num_bands is positive",
+            });
+        }
+        if !(self.solver_tolerance.is_finite() && self.solver_tolerance >= 0.0) {
+            return Err(ConfigError::OutOfRange {
+                field: "solver_tolerance",
+                value: self.solver_tolerance,
+                expected: "finite and non-negative",
+            });
+        }
+        for (field, value) in [("density", self.density), ("dt", self.dt), ("h", self.h)] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(ConfigError::OutOfRange {
+                    field,
+                    value,
+                    expected: "finite and positive",
+                });
+            }
+        }
+        for (field, value) in [
+            ("vorticity_confinement", self.vorticity_confinement),
+            ("viscosity", self.viscosity),
+            ("thermal_diffusivity", self.thermal_diffusivity),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(ConfigError::OutOfRange {
+                    field,
+                    value,
+                    expected: "finite and non-negative",
+                });
+            }
+        }
+        for (field, value) in [
+            ("gravity", self.gravity),
+            ("windspeed", self.windspeed),
+            ("ambient_temperature", self.ambient_temperature),
+            ("thermal_expansion", self.thermal_expansion),
+            ("smoke_weight", self.smoke_weight),
+            ("body_force", self.body_force.0),
+            ("body_force", self.body_force.1),
+        ] {
+            if !value.is_finite() {
+                return Err(ConfigError::OutOfRange {
+                    field,
+                    value,
+                    expected: "finite",
+                });
+            }
+        }
+        let b = &self.boundaries;
+        if (b.left == Boundary::Periodic) != (b.right == Boundary::Periodic) {
+            return Err(ConfigError::Inconsistent {
+                field: "boundaries",
+                expected: "left and right both periodic or neither",
+            });
+        }
+        if (b.top == Boundary::Periodic) != (b.bottom == Boundary::Periodic) {
+            return Err(ConfigError::Inconsistent {
+                field: "boundaries",
+                expected: "top and bottom both periodic or neither",
+            });
+        }
+        if let Timestep::Adaptive { cfl, max_substeps } = self.timestep {
+            if !(cfl.is_finite() && cfl > 0.0) {
+                return Err(ConfigError::OutOfRange {
+                    field: "timestep.cfl",
+                    value: cfl,
+                    expected: "finite and positive",
+                });
+            }
+            if max_substeps == 0 {
+                return Err(ConfigError::OutOfRange {
+                    field: "timestep.max_substeps",
+                    value: 0.0,
+                    expected: "at least 1",
+                });
+            }
+        }
+        self.validate_inflow()
+    }
+
+    fn validate_inflow(&self) -> Result<(), ConfigError> {
+        let positive = |field, value: f64| {
+            if value.is_finite() && value > 0.0 {
+                Ok(())
+            } else {
+                Err(ConfigError::OutOfRange {
+                    field,
+                    value,
+                    expected: "finite and positive",
+                })
+            }
+        };
+        let finite = |field, value: f64| {
+            if value.is_finite() {
+                Ok(())
+            } else {
+                Err(ConfigError::OutOfRange {
+                    field,
+                    value,
+                    expected: "finite",
+                })
+            }
+        };
+        let table = |field, table: &[(f64, f64)]| {
+            if valid_table(table) {
+                Ok(())
+            } else {
+                Err(ConfigError::Inconsistent {
+                    field,
+                    expected: "a non-empty finite table with increasing keys",
+                })
+            }
+        };
+        match &self.inflow_shape {
+            InflowShape::Uniform | InflowShape::Parabolic => {}
+            InflowShape::BoundaryLayer { thickness } => {
+                positive("inflow_shape.thickness", *thickness)?
+            }
+            InflowShape::Table(rows) => table("inflow_shape", rows)?,
+        }
+        match &self.inflow_modulation {
+            InflowModulation::Steady => {}
+            InflowModulation::Pulsating { amplitude, period } => {
+                finite("inflow_modulation.amplitude", *amplitude)?;
+                positive("inflow_modulation.period", *period)?;
+            }
+            InflowModulation::Gust {
+                start,
+                duration,
+                amplitude,
+            } => {
+                finite("inflow_modulation.start", *start)?;
+                positive("inflow_modulation.duration", *duration)?;
+                finite("inflow_modulation.amplitude", *amplitude)?;
+            }
+            InflowModulation::Table(rows) => table("inflow_modulation", rows)?,
+        }
+        Ok(())
+    }
+}
+
+pub struct SimConfigBuilder {
+    config: SimConfig,
+}
+
+macro_rules! builder_setter {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        impl SimConfigBuilder {
+            $(
+                pub fn $field(mut self, $field: $ty) -> Self {
+                    self.config.$field = $field;
+                    self
+                }
+            )*
+        }
+    };
+}
+
+builder_setter!(
+    overrelaxation: f64,
+    projection_iterations: u32,
+    gravity: f64,
+    density: f64,
+    windspeed: f64,
+    dt: f64,
+    h: f64,
+    band_width: usize,
+    num_bands: usize,
+    with_gravity: bool,
+    body_force: (f64, f64),
+    draw_obstacle: bool,
+    cut_cells: bool,
+    pressure_solver: PressureSolver,
+    solver_tolerance: f64,
+    max_solver_iterations: u32,
+    divergence_norm: DivergenceNorm,
+    sor_until_converged: bool,
+    sor_ordering: SorOrdering,
+    advection: AdvectionScheme,
+    backtrace: Backtrace,
+    interpolation: Interpolation,
+    vorticity_confinement: f64,
+    viscosity: f64,
+    diffusion: Diffusion,
+    diffusion_iterations: u32,
+    ambient_temperature: f64,
+    thermal_expansion: f64,
+    smoke_weight: f64,
+    thermal_diffusivity: f64,
+    boundaries: Boundaries,
+    inflow_shape: InflowShape,
+    inflow_modulation: InflowModulation,
+    timestep: Timestep,
+);
+
+impl SimConfigBuilder {
+    pub fn build(self) -> Result<SimConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    OutOfRange {
+        field: &'static str,
+        value: f64,
+        expected: &'static str,
+    },
+    Inconsistent {
+        field: &'static str,
+        expected: &'static str,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::OutOfRange {
+                field,
+                value,
+                expected,
+            } => write!(f, "{} = {} must be {}", field, value, expected),
+            ConfigError::Inconsistent { field, expected } => {
+                write!(f, "{}: expected {}", field, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigError, InflowModulation, InflowShape, SimConfig};
+
+    #[test]
+    fn default_is_valid() {
+        assert_eq!(SimConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_zero_diffusion_iterations() {
+        assert!(matches!(
+            SimConfig::builder().diffusion_iterations(0).build(),
+            Err(ConfigError::OutOfRange {
+                field: "diffusion_iterations",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn smoke_bands_need_a_width() {
+        assert!(matches!(
+            SimConfig::builder().band_width(0).build(),
+            Err(ConfigError::OutOfRange {
+                field: "band_width",
+                ..
+            })
+        ));
+        let no_bands = SimConfig::builder().band_width(0).num_bands(0).build();
+        assert!(no_bands.is_ok());
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "{} vs {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn inflow_shapes() {
+        assert_eq!(InflowShape::Uniform.factor(0.1, 8.0), 1.0);
+
+        let parabolic = InflowShape::Parabolic;
+        assert_close(parabolic.factor(0.5, 8.0), 1.0);
+        assert_close(parabolic.factor(0.25, 8.0), 0.75);
+        assert_close(parabolic.factor(0.0, 8.0), 0.0);
+
+        // Two units thick on an edge eight units long: the layer ends a
+        // quarter of the way in from either end.
+        let layer = InflowShape::BoundaryLayer { thickness: 2.0 };
+        assert_close(layer.factor(0.0, 8.0), 0.0);
+        assert_close(layer.factor(0.125, 8.0), 1.0 - 0.25 + 0.0625);
+        assert_close(layer.factor(0.25, 8.0), 1.0);
+        assert_close(layer.factor(0.5, 8.0), 1.0);
+        assert_close(layer.factor(0.875, 8.0), 1.0 - 0.25 + 0.0625);
+
+        let table = InflowShape::Table(vec![(0.2, 0.5), (0.6, 1.5), (0.8, 1.0)]);
+        assert_close(table.factor(0.0, 8.0), 0.5);
+        assert_close(table.factor(0.2, 8.0), 0.5);
+        assert_close(table.factor(0.3, 8.0), 0.75);
+        assert_close(table.factor(0.7, 8.0), 1.25);
+        assert_close(table.factor(0.8, 8.0), 1.0);
+        assert_close(table.factor(1.0, 8.0), 1.0);
+    }
+
+    #[test]
+    fn inflow_modulations() {
+        assert_eq!(InflowModulation::Steady.factor(3.0), 1.0);
+
+        let pulsating = InflowModulation::Pulsating {
+            amplitude: 0.5,
+            period: 4.0,
+        };
+        assert_close(pulsating.factor(0.0), 1.0);
+        assert_close(pulsating.factor(1.0), 1.5);
+        assert_close(pulsating.factor(3.0), 0.5);
+        assert_close(pulsating.factor(5.0), 1.5);
+
+        let gust = InflowModulation::Gust {
+            start: 2.0,
+            duration: 4.0,
+            amplitude: 0.8,
+        };
+        assert_close(gust.factor(1.0), 1.0);
+        assert_close(gust.factor(2.0), 1.0);
+        assert_close(gust.factor(3.0), 1.4);
+        assert_close(gust.factor(4.0), 1.8);
+        assert_close(gust.factor(6.0), 1.0);
+        assert_close(gust.factor(7.0), 1.0);
+
+        let table = InflowModulation::Table(vec![(1.0, 0.0), (3.0, 2.0)]);
+        assert_close(table.factor(0.0), 0.0);
+        assert_close(table.factor(2.5), 1.5);
+        assert_close(table.factor(10.0), 2.0);
+    }
+}
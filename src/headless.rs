@@ -0,0 +1,288 @@
+use crate::config::SimConfig;
+use crate::sim::{Probe, Simulation};
+use crate::util::max_or_nan;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "usage: euler headless [--width N] [--height N] [--steps N] \
+[--max-wall SECONDS] [--max-time SIM_SECONDS] [--every N] [--fields smoke,pressure,speed,curl,temperature] \
+[--out DIR] [--scene FILE]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Smoke,
+    Pressure,
+    Speed,
+    Curl,
+    Temperature,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Smoke => "smoke",
+            Field::Pressure => "pressure",
+            Field::Speed => "speed",
+            Field::Curl => "curl",
+            Field::Temperature => "temperature",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "smoke" => Ok(Field::Smoke),
+            "pressure" => Ok(Field::Pressure),
+            "speed" => Ok(Field::Speed),
+            "curl" => Ok(Field::Curl),
+            "temperature" => Ok(Field::Temperature),
+            _ => Err(format!("unknown field '{}'", name)),
+        }
+    }
+}
+
+/// Stopping criteria and outputs for a run without a window.
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub width: usize,
+    pub height: usize,
+    pub steps: Option<u64>,
+    pub max_wall: Option<Duration>,
+    pub max_time: Option<f64>,
+    pub every: Option<u64>,
+    pub fields: Vec<Field>,
+    pub out: PathBuf,
+    /// Scene file to start from; its grid size replaces `width` and `height`.
+    pub scene: Option<PathBuf>,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        HeadlessOptions {
+            width: 200,
+            height: 200,
+            steps: None,
+            max_wall: None,
+            max_time: None,
+            every: None,
+            fields: vec![Field::Smoke],
+            out: PathBuf::from("out"),
+            scene: None,
+        }
+    }
+}
+
+impl HeadlessOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut opts = HeadlessOptions::default();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", flag))
+            };
+            match flag.as_str() {
+                "--width" => opts.width = parse_num(flag, value()?)?,
+                "--height" => opts.height = parse_num(flag, value()?)?,
+                "--steps" => opts.steps = Some(parse_num(flag, value()?)?),
+                "--max-wall" => {
+                    opts.max_wall = Some(Duration::from_secs_f64(parse_num(flag, value()?)?))
+                }
+                "--max-time" => opts.max_time = Some(parse_num(flag, value()?)?),
+                "--every" => opts.every = Some(parse_num(flag, value()?)?),
+                "--fields" => {
+                    opts.fields = value()?
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .map(Field::parse)
+                        .collect::<Result<_, _>>()?
+                }
+                "--out" => opts.out = PathBuf::from(value()?),
+                "--scene" => opts.scene = Some(PathBuf::from(value()?)),
+                _ => return Err(format!("unknown argument '{}'", flag)),
+            }
+        }
+
+        if opts.width == 0 || opts.height == 0 {
+            return Err("grid dimensions must be positive".to_string());
+        }
+        if opts.every == Some(0) {
+            return Err("--every must be at least 1".to_string());
+        }
+        if opts.steps.is_none() && opts.max_wall.is_none() && opts.max_time.is_none() {
+            return Err("one of --steps, --max-wall or --max-time is required".to_string());
+        }
+        Ok(opts)
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+/// Steps a `Simulation` until the first stopping criterion is met, writing the
+/// requested fields and a per-step `diagnostics.csv` into `opts.out`.
+pub fn run(opts: &HeadlessOptions, config: SimConfig) -> io::Result<()> {
+    run_simulation(opts, Simulation::new(opts.width, opts.height, config), &[])
+}
+
+/// Like `run`, but starting from an already set up simulation, e.g. one built
+/// from a scene. With any `probes`, their samples after every step go to
+/// `probes.csv`.
+pub fn run_simulation(
+    opts: &HeadlessOptions,
+    mut sim: Simulation,
+    probes: &[Probe],
+) -> io::Result<()> {
+    fs::create_dir_all(&opts.out)?;
+
+    let mut diagnostics = BufWriter::new(File::create(opts.out.join("diagnostics.csv"))?);
+    writeln!(
+        diagnostics,
+        "step,sim_time,wall_time,max_speed,min_pressure,max_pressure,total_smoke,\
+solver_iterations,solver_residual,max_div_before,rms_div_before,max_div_after,rms_div_after,\
+dt,substeps"
+    )?;
+
+    let mut probe_log = if probes.is_empty() {
+        None
+    } else {
+        let mut log = BufWriter::new(File::create(opts.out.join("probes.csv"))?);
+        writeln!(log, "step,sim_time,probe,u,v,pressure,smoke,temperature")?;
+        Some(log)
+    };
+
+    let start = Instant::now();
+    let mut step: u64 = 0;
+    loop {
+        let done = opts.steps.is_some_and(|steps| step >= steps)
+            || opts.max_wall.is_some_and(|limit| start.elapsed() >= limit)
+            || opts.max_time.is_some_and(|limit| sim.time() >= limit);
+        if done {
+            break;
+        }
+
+        let stats = sim.step();
+        step += 1;
+
+        let speed = sim.get_speed();
+        let pressure = sim.get_pressure();
+        writeln!(
+            diagnostics,
+            "{},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{},{:.3e},{:.3e},{:.3e},{:.3e},{:.3e},{:.6},{}",
+            step,
+            sim.time(),
+            start.elapsed().as_secs_f64(),
+            speed.iter().fold(0.0, |acc, &x| max_or_nan(acc, x)),
+            pressure.iter().fold(f64::MAX, |acc, &x| acc.min(x)),
+            pressure.iter().fold(f64::MIN, |acc, &x| acc.max(x)),
+            sim.get_smoke().iter().sum::<f64>(),
+            stats.solve.iterations,
+            stats.solve.residual,
+            stats.divergence_before.max,
+            stats.divergence_before.rms,
+            stats.divergence_after.max,
+            stats.divergence_after.rms,
+            stats.dt,
+            stats.substeps,
+        )?;
+
+        if let Some(log) = &mut probe_log {
+            for probe in probes {
+                let sample = sim.probe(probe.x, probe.y);
+                writeln!(
+                    log,
+                    "{},{:.6},{},{:.6},{:.6},{:.6},{:.6},{:.6}",
+                    step,
+                    sim.time(),
+                    probe.name,
+                    sample.u,
+                    sample.v,
+                    sample.pressure,
+                    sample.smoke,
+                    sample.temperature,
+                )?;
+            }
+        }
+
+        let divergence = stats.divergence_after;
+        if !(divergence.max.is_finite() && divergence.rms.is_finite()) {
+            diagnostics.flush()?;
+            return Err(io::Error::other(format!(
+                "simulation blew up at step {}",
+                step
+            )));
+        }
+
+        if opts.every.is_some_and(|every| step.is_multiple_of(every)) {
+            write_fields(&sim, opts, &format!("{:06}", step))?;
+        }
+    }
+    diagnostics.flush()?;
+    if let Some(log) = &mut probe_log {
+        log.flush()?;
+    }
+
+    write_fields(&sim, opts, "final")?;
+    println!(
+        "{} steps ({:.3} sim s) in {:.3} s, output in {}",
+        step,
+        sim.time(),
+        start.elapsed().as_secs_f64(),
+        opts.out.display()
+    );
+    Ok(())
+}
+
+fn write_fields(sim: &Simulation, opts: &HeadlessOptions, tag: &str) -> io::Result<()> {
+    for &field in &opts.fields {
+        let speed;
+        let data = match field {
+            Field::Smoke => sim.get_smoke(),
+            Field::Pressure => sim.get_pressure(),
+            Field::Curl => sim.get_curl(),
+            Field::Temperature => sim.get_temperature(),
+            Field::Speed => {
+                speed = sim.get_speed();
+                &speed
+            }
+        };
+        let path = opts.out.join(format!("{}_{}.csv", field.name(), tag));
+        write_csv(&path, data, sim.width())?;
+    }
+    Ok(())
+}
+
+fn write_csv(path: &PathBuf, data: &[f64], width: usize) -> io::Result<()> {
+    let mut output = BufWriter::new(File::create(path)?);
+    for row in data.chunks(width) {
+        let line: Vec<String> = row.iter().map(|x| format!("{:.6}", x)).collect();
+        writeln!(output, "{}", line.join(","))?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeadlessOptions, run};
+    use crate::sim::testing::unstable_config;
+
+    #[test]
+    fn blow_up_stops_the_run() {
+        let out = std::env::temp_dir().join(format!("euler-blow-up-{}", std::process::id()));
+        let opts = HeadlessOptions {
+            width: 40,
+            height: 30,
+            steps: Some(60),
+            out: out.clone(),
+            ..HeadlessOptions::default()
+        };
+        let result = run(&opts, unstable_config());
+        let _ = std::fs::remove_dir_all(&out);
+        let error = result.expect_err("run finished despite blowing up");
+        assert!(error.to_string().contains("blew up"), "{}", error);
+    }
+}
@@ -0,0 +1,458 @@
+//! Minimal image loading for obstacle masks and smoke source maps.
+//!
+//! Netpbm files (PBM, PGM and PPM, plain or raw) are always readable; PNG
+//! needs the default `png` feature.
+
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Largest width or height accepted from a Netpbm header.
+const MAX_SIDE: usize = 65535;
+
+/// A decoded image with every sample scaled to `[0, 1]`. Row 0 is the top of
+/// the picture, which lines up with the `y = 0` row of the grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Red, green, blue and alpha of each pixel, in row-major order.
+    pixels: Vec<[f64; 4]>,
+}
+
+/// One value read from each pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ImageChannel {
+    /// Perceived brightness, ignoring alpha.
+    Luma,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+/// How a pixel decides whether the cell it covers is solid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MaskMode {
+    /// Pixels darker than the threshold are solid, so black shapes drawn on
+    /// white make obstacles.
+    Dark(f64),
+    /// Pixels more opaque than the threshold are solid, for shapes painted on
+    /// a transparent layer.
+    Opaque(f64),
+}
+
+impl MaskMode {
+    pub fn is_solid(&self, image: &Image, x: usize, y: usize, width: usize, height: usize) -> bool {
+        match *self {
+            MaskMode::Dark(threshold) => {
+                image.sample(ImageChannel::Luma, x, y, width, height) < threshold
+            }
+            MaskMode::Opaque(threshold) => {
+                image.sample(ImageChannel::Alpha, x, y, width, height) > threshold
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The file is damaged or not in the format it claims to be.
+    Format(String),
+    /// A valid file this loader does not read.
+    Unsupported(String),
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "{}", e),
+            ImageError::Format(message) => write!(f, "malformed image: {}", message),
+            ImageError::Unsupported(message) => write!(f, "unsupported image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+impl Image {
+    /// Reads a PNG or Netpbm file, telling them apart by content rather than
+    /// by extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ImageError> {
+        if bytes.starts_with(b"\x89PNG") {
+            decode_png(bytes)
+        } else if bytes.first() == Some(&b'P') {
+            decode_netpbm(bytes)
+        } else {
+            Err(ImageError::Unsupported(
+                "expected a PNG, PBM, PGM or PPM file".to_string(),
+            ))
+        }
+    }
+
+    /// Value of `channel` at pixel `(x, y)`.
+    pub fn get(&self, channel: ImageChannel, x: usize, y: usize) -> f64 {
+        let [r, g, b, a] = self.pixels[y * self.width + x];
+        match channel {
+            ImageChannel::Luma => 0.299 * r + 0.587 * g + 0.114 * b,
+            ImageChannel::Red => r,
+            ImageChannel::Green => g,
+            ImageChannel::Blue => b,
+            ImageChannel::Alpha => a,
+        }
+    }
+
+    /// Nearest-neighbour sample of `channel` for cell `(x, y)` when the image
+    /// is stretched over a `width` by `height` grid.
+    pub fn sample(
+        &self,
+        channel: ImageChannel,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> f64 {
+        let px = ((x as f64 + 0.5) * self.width as f64 / width as f64) as usize;
+        let py = ((y as f64 + 0.5) * self.height as f64 / height as f64) as usize;
+        self.get(channel, px.min(self.width - 1), py.min(self.height - 1))
+    }
+}
+
+#[cfg(feature = "png")]
+fn decode_png(bytes: &[u8]) -> Result<Image, ImageError> {
+    let format = |e: png::DecodingError| match e {
+        png::DecodingError::IoError(e) => ImageError::Io(e),
+        e => ImageError::Format(e.to_string()),
+    };
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(format)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(format)?;
+    let samples = info.color_type.samples();
+    let pixels = buffer[..info.buffer_size()]
+        .chunks_exact(samples)
+        .map(|p| {
+            let p: Vec<f64> = p.iter().map(|&v| v as f64 / 255.0).collect();
+            match p.len() {
+                1 => [p[0], p[0], p[0], 1.0],
+                2 => [p[0], p[0], p[0], p[1]],
+                3 => [p[0], p[1], p[2], 1.0],
+                _ => [p[0], p[1], p[2], p[3]],
+            }
+        })
+        .collect();
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+#[cfg(not(feature = "png"))]
+fn decode_png(_: &[u8]) -> Result<Image, ImageError> {
+    Err(ImageError::Unsupported(
+        "built without the `png` feature".to_string(),
+    ))
+}
+
+/// Decodes the P1 to P6 Netpbm formats. PBM bits are 1 for black.
+fn decode_netpbm(bytes: &[u8]) -> Result<Image, ImageError> {
+    let mut header = Header { bytes, pos: 2 };
+    let kind = bytes.get(1).copied().unwrap_or(0);
+    let (plain, channels) = match kind {
+        b'1' => (true, 0),
+        b'2' => (true, 1),
+        b'3' => (true, 3),
+        b'4' => (false, 0),
+        b'5' => (false, 1),
+        b'6' => (false, 3),
+        _ => {
+            return Err(ImageError::Unsupported(
+                "only P1 to P6 Netpbm files are read".to_string(),
+            ));
+        }
+    };
+    let width = header.number()?;
+    let height = header.number()?;
+    let max = if channels == 0 { 1 } else { header.number()? };
+    if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE || max == 0 || max > 65535
+    {
+        return Err(ImageError::Format(format!(
+            "bad dimensions {}x{} or maximum {}",
+            width, height, max
+        )));
+    }
+
+    let count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(channels.max(1)))
+        .ok_or_else(too_large)?;
+    let values: Vec<usize> = if plain {
+        (0..count)
+            .map(|_| {
+                if channels == 0 {
+                    header.bit()
+                } else {
+                    header.number()
+                }
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        // A single whitespace byte separates the header from the raster.
+        let data = bytes.get(header.pos + 1..).unwrap_or(&[]);
+        if channels == 0 {
+            let row_bytes = width.div_ceil(8);
+            if data.len() < row_bytes.checked_mul(height).ok_or_else(too_large)? {
+                return Err(truncated());
+            }
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (y, x)))
+                .map(|(y, x)| ((data[y * row_bytes + x / 8] >> (7 - x % 8)) & 1) as usize)
+                .collect()
+        } else {
+            let size = if max > 255 { 2 } else { 1 };
+            if data.len() < count.checked_mul(size).ok_or_else(too_large)? {
+                return Err(truncated());
+            }
+            data.chunks_exact(size)
+                .take(count)
+                .map(|v| v.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+                .collect()
+        }
+    };
+    if values.iter().any(|&v| v > max) {
+        return Err(ImageError::Format(format!("sample above maximum {}", max)));
+    }
+
+    let scale = |v: usize| v as f64 / max as f64;
+    let pixels = match channels {
+        0 => values
+            .iter()
+            .map(|&bit| {
+                let v = 1.0 - bit as f64;
+                [v, v, v, 1.0]
+            })
+            .collect(),
+        1 => values
+            .iter()
+            .map(|&v| [scale(v), scale(v), scale(v), 1.0])
+            .collect(),
+        _ => values
+            .chunks_exact(3)
+            .map(|p| [scale(p[0]), scale(p[1]), scale(p[2]), 1.0])
+            .collect(),
+    };
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn too_large() -> ImageError {
+    ImageError::Format("image is too large".to_string())
+}
+
+fn truncated() -> ImageError {
+    ImageError::Format("raster data is truncated".to_string())
+}
+
+/// Reader for the whitespace-separated header and plain raster of a Netpbm
+/// file, skipping `#` comments.
+struct Header<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Header<'_> {
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.bytes.get(self.pos) {
+            if b == b'#' {
+                while self.bytes.get(self.pos).is_some_and(|&b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, ImageError> {
+        self.skip_space();
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| ImageError::Format(format!("expected a number at byte {}", start)))
+    }
+
+    /// One digit of a plain PBM raster, where bits need not be separated.
+    fn bit(&mut self) -> Result<usize, ImageError> {
+        self.skip_space();
+        match self.bytes.get(self.pos) {
+            Some(&b @ (b'0' | b'1')) => {
+                self.pos += 1;
+                Ok((b - b'0') as usize)
+            }
+            _ => Err(ImageError::Format(format!(
+                "expected 0 or 1 at byte {}",
+                self.pos
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Image, ImageChannel, ImageError, MaskMode};
+
+    fn channel(image: &Image, channel: ImageChannel) -> Vec<f64> {
+        (0..image.height)
+            .flat_map(|y| (0..image.width).map(move |x| (x, y)))
+            .map(|(x, y)| image.get(channel, x, y))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_plain_and_raw_bitmaps() {
+        let plain = Image::decode(b"P1\n# a comment\n3 2\n1 0 1\n011").unwrap();
+        // Rows of a raw PBM are padded to whole bytes.
+        let raw = Image::decode(b"P4\n3 2\n\xa0\x60").unwrap();
+        for image in [plain, raw] {
+            assert_eq!((image.width, image.height), (3, 2));
+            assert_eq!(
+                channel(&image, ImageChannel::Red),
+                [0.0, 1.0, 0.0, 1.0, 0.0, 0.0]
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_grey_and_colour_maps() {
+        let plain = Image::decode(b"P2 2 1 4 0 4").unwrap();
+        let raw = Image::decode(b"P5 2 1 4\n\x00\x04").unwrap();
+        let wide = Image::decode(b"P5 2 1 1000\n\x00\x00\x03\xe8").unwrap();
+        for image in [plain, raw, wide] {
+            assert_eq!(channel(&image, ImageChannel::Red), [0.0, 1.0]);
+            assert_eq!(channel(&image, ImageChannel::Alpha), [1.0, 1.0]);
+        }
+
+        let plain = Image::decode(b"P3 2 1 255  255 0 0  0 51 255").unwrap();
+        let raw = Image::decode(b"P6 2 1 255\n\xff\x00\x00\x00\x33\xff").unwrap();
+        for image in [plain, raw] {
+            assert_eq!(channel(&image, ImageChannel::Red), [1.0, 0.0]);
+            assert_eq!(channel(&image, ImageChannel::Green), [0.0, 0.2]);
+            assert_eq!(channel(&image, ImageChannel::Blue), [0.0, 1.0]);
+            assert!((image.get(ImageChannel::Luma, 0, 0) - 0.299).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        for bytes in [
+            &b"P5 2 2 255\n\x00\x00\x00"[..],
+            b"P4 9 1\n\x00",
+            b"P2 2 1 3 0 4",
+            b"P2 2 1 255 0",
+            b"P1 2 1 0 2",
+            b"P2 0 1 255",
+            b"P3 1 1 0 0 0 0",
+        ] {
+            assert!(
+                matches!(Image::decode(bytes), Err(ImageError::Format(_))),
+                "{:?}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+        for bytes in [&b"P7 1 1"[..], b"GIF89a", b""] {
+            assert!(matches!(
+                Image::decode(bytes),
+                Err(ImageError::Unsupported(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn samples_stretch_over_the_grid() {
+        let image = Image::decode(b"P1 2 2 1 0 0 1").unwrap();
+        let dark = |x, y| image.sample(ImageChannel::Luma, x, y, 4, 6) < 0.5;
+        assert!(dark(0, 0) && dark(1, 2) && dark(3, 5) && dark(2, 3));
+        assert!(!dark(2, 0) && !dark(3, 2) && !dark(0, 3) && !dark(1, 5));
+        assert!(MaskMode::Dark(0.5).is_solid(&image, 0, 0, 4, 6));
+        // Netpbm images have no alpha, so every pixel is opaque.
+        assert!(MaskMode::Opaque(0.5).is_solid(&image, 2, 0, 4, 6));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn decodes_png() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer
+                .write_image_data(&[255, 0, 0, 255, 0, 0, 255, 0])
+                .unwrap();
+        }
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(channel(&image, ImageChannel::Red), [1.0, 0.0]);
+        assert_eq!(channel(&image, ImageChannel::Blue), [0.0, 1.0]);
+        assert_eq!(channel(&image, ImageChannel::Alpha), [1.0, 0.0]);
+        assert!(MaskMode::Opaque(0.5).is_solid(&image, 0, 0, 2, 1));
+        assert!(!MaskMode::Opaque(0.5).is_solid(&image, 1, 0, 2, 1));
+    }
+
+    #[test]
+    fn oversized_headers_are_rejected() {
+        for header in [
+            &b"P5\n99999999999 99999999999\n255\n"[..],
+            b"P4\n99999999999 99999999999\n",
+            b"P2\n65536 1\n255\n0",
+            b"P6\n99999999999999999999999 2\n255\n",
+        ] {
+            assert!(
+                matches!(Image::decode(header), Err(ImageError::Format(_))),
+                "{:?}",
+                String::from_utf8_lossy(header)
+            );
+        }
+    }
+}
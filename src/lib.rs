@@ -0,0 +1,36 @@
+//! Eulerian fluid solver on a staggered MAC grid.
+//!
+//! The solver itself has no windowing dependencies; the interactive viewer in
+//! [`vis`] is only compiled with the default `viewer` feature, and PNG masks
+//! in [`image`] need the default `png` feature. Scene files in `scene` need
+//! the default `scene` feature.
+
+pub mod airfoil;
+pub mod config;
+pub mod headless;
+pub mod image;
+#[cfg(feature = "scene")]
+pub mod scene;
+pub mod shape;
+pub mod sim;
+pub mod svg;
+pub mod util;
+#[cfg(feature = "viewer")]
+pub mod vis;
+
+pub use airfoil::{Airfoil, NacaProfile, ParseNacaError};
+pub use config::{
+    AdvectionScheme, Backtrace, Boundaries, Boundary, ConfigError, Diffusion, DivergenceNorm,
+    InflowModulation, InflowShape, Interpolation, MultigridCycle, PressureSolver, SimConfig,
+    SimConfigBuilder, SorOrdering, Timestep,
+};
+pub use image::{Image, ImageChannel, ImageError, MaskMode};
+#[cfg(feature = "scene")]
+pub use scene::{Obstacle, Scene, SceneError, SmokeMap};
+pub use shape::Shape;
+pub use sim::{
+    DivergenceStats, HeatSource, Probe, ProbeSample, ScalarChannel, ScalarSource, Simulation,
+    SolveStats, StepStats,
+};
+pub use svg::{Svg, SvgError, SvgPath};
+pub use util::Array2D;
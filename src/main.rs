@@ -1,8 +1,83 @@
-mod sim;
-mod vis;
-use vis::Visualization;
-mod util;
+use euler::SimConfig;
+use euler::headless::{self, HeadlessOptions};
+use std::path::Path;
+
 fn main() {
-    let euler = Visualization::new(200, 200);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("headless") => {
+            let opts = HeadlessOptions::parse(&args[1..]).unwrap_or_else(|e| {
+                eprintln!("{}\n{}", e, headless::USAGE);
+                std::process::exit(2);
+            });
+            let result = match &opts.scene {
+                Some(path) => {
+                    let (sim, probes, _) = load_scene(path);
+                    headless::run_simulation(&opts, sim, &probes)
+                }
+                None => headless::run(&opts, SimConfig::default()),
+            };
+            if let Err(e) = result {
+                eprintln!("headless run failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("--scene") => match args.get(1) {
+            Some(path) if args.len() == 2 => view(Some(Path::new(path))),
+            _ => {
+                eprintln!("usage: euler [--scene FILE]\n{}", headless::USAGE);
+                std::process::exit(2);
+            }
+        },
+        Some(other) => {
+            eprintln!("unknown command '{}'\n{}", other, headless::USAGE);
+            std::process::exit(2);
+        }
+        None => view(None),
+    }
+}
+
+/// The simulation, probes and first airfoil a scene file describes, exiting
+/// with a message if it cannot be read.
+#[cfg(feature = "scene")]
+fn load_scene(path: &Path) -> (euler::Simulation, Vec<euler::Probe>, Option<euler::Airfoil>) {
+    let built = euler::Scene::load(path).and_then(|scene| {
+        let airfoil = scene.airfoil()?;
+        Ok((scene.build()?, scene.probes, airfoil))
+    });
+    built.unwrap_or_else(|e| {
+        eprintln!("could not load scene {}: {}", path.display(), e);
+        std::process::exit(1);
+    })
+}
+
+#[cfg(not(feature = "scene"))]
+fn load_scene(_: &Path) -> (euler::Simulation, Vec<euler::Probe>, Option<euler::Airfoil>) {
+    eprintln!("built without the `scene` feature");
+    std::process::exit(2);
+}
+
+#[cfg(feature = "viewer")]
+fn view(scene: Option<&Path>) {
+    let euler = match scene {
+        Some(path) => {
+            let (sim, _, airfoil) = load_scene(path);
+            let euler = euler::vis::Visualization::with_simulation(sim);
+            match airfoil {
+                Some(airfoil) => euler.with_airfoil(airfoil),
+                None => euler,
+            }
+        }
+        None => euler::vis::Visualization::new(200, 200, SimConfig::default()),
+    };
     euler.run();
 }
+
+#[cfg(not(feature = "viewer"))]
+fn view(_: Option<&Path>) {
+    eprintln!(
+        "built without the `viewer` feature; use the headless subcommand\n{}",
+        headless::USAGE
+    );
+    std::process::exit(2);
+}
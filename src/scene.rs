@@ -0,0 +1,451 @@
+//! Reproducible setups stored as TOML or JSON scene files.
+//!
+//! A scene holds everything `Simulation::new` cannot infer: the grid size,
+//! the configuration including boundaries, obstacles, heat and smoke sources,
+//! scalar channels, and probes for the headless runner. Every field may be
+//! left out to keep its default, so a scene file only needs to list what
+//! differs from the built-in wind tunnel.
+
+use crate::airfoil::{Airfoil, ParseNacaError};
+use crate::config::{ConfigError, SimConfig};
+use crate::image::{Image, ImageChannel, ImageError, MaskMode};
+use crate::shape::Shape;
+use crate::sim::{HeatSource, Probe, ScalarChannel, Simulation};
+use crate::svg::{Svg, SvgError};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scene {
+    pub width: usize,
+    pub height: usize,
+    pub config: SimConfig,
+    /// Drawn in order after the obstacle `config.draw_obstacle` places.
+    pub obstacles: Vec<Obstacle>,
+    pub heat_sources: Vec<HeatSource>,
+    pub smoke_map: Option<SmokeMap>,
+    /// Added in order, so the viewer's `Dyes` mode steps through them in the
+    /// order they are listed.
+    pub channels: Vec<ScalarChannel>,
+    pub probes: Vec<Probe>,
+    /// Directory that relative file paths are resolved against.
+    #[serde(skip)]
+    directory: PathBuf,
+}
+
+/// One entry of a scene's obstacle list, in the cell units of `Shape`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Obstacle {
+    Shape(Shape),
+    /// A NACA section such as `"2412"`, placed by its quarter chord.
+    Airfoil {
+        naca: String,
+        chord: f64,
+        position: (f64, f64),
+        #[serde(default)]
+        angle_of_attack_degrees: f64,
+    },
+    /// Outlines from an SVG file, fitted into the box from `min` to `max`.
+    Svg {
+        path: PathBuf,
+        min: (f64, f64),
+        max: (f64, f64),
+    },
+    /// An image stretched over the grid, replacing every obstacle drawn
+    /// before it.
+    Mask {
+        path: PathBuf,
+        mode: MaskMode,
+    },
+}
+
+/// An image channel holding the smoke of each cell, as in
+/// `Simulation::set_smoke_sources`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmokeMap {
+    pub path: PathBuf,
+    pub channel: ImageChannel,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    /// The scene file is not valid TOML or JSON, or does not describe a scene.
+    Parse(String),
+    /// A grid with no cells.
+    EmptyGrid,
+    Config(ConfigError),
+    Naca(ParseNacaError),
+    Image(PathBuf, ImageError),
+    Svg(PathBuf, SvgError),
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{}", e),
+            SceneError::Parse(message) => write!(f, "invalid scene: {}", message),
+            SceneError::EmptyGrid => write!(f, "grid dimensions must be positive"),
+            SceneError::Config(e) => write!(f, "{}", e),
+            SceneError::Naca(e) => write!(f, "{}", e),
+            SceneError::Image(path, e) => write!(f, "{}: {}", path.display(), e),
+            SceneError::Svg(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl Default for Scene {
+    /// The setup the viewer and headless runner use without a scene file.
+    fn default() -> Self {
+        Scene {
+            width: 200,
+            height: 200,
+            config: SimConfig::default(),
+            obstacles: Vec::new(),
+            heat_sources: Vec::new(),
+            smoke_map: None,
+            channels: Vec::new(),
+            probes: Vec::new(),
+            directory: PathBuf::new(),
+        }
+    }
+}
+
+impl Scene {
+    /// Reads a scene, as JSON if the file name ends in `.json` and as TOML
+    /// otherwise. Paths inside it are relative to the file's directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut scene = if is_json(path) {
+            Self::from_json(&text)?
+        } else {
+            Self::from_toml(&text)?
+        };
+        scene.directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(scene)
+    }
+
+    /// Writes the scene in the format `load` would read it back in.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let text = if is_json(path) {
+            self.to_json()?
+        } else {
+            self.to_toml()?
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, SceneError> {
+        toml::from_str(text).map_err(|e| SceneError::Parse(e.to_string()))
+    }
+
+    pub fn to_toml(&self) -> Result<String, SceneError> {
+        toml::to_string_pretty(self).map_err(|e| SceneError::Parse(e.to_string()))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, SceneError> {
+        serde_json::from_str(text).map_err(|e| SceneError::Parse(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        serde_json::to_string_pretty(self).map_err(|e| SceneError::Parse(e.to_string()))
+    }
+
+    /// Creates the simulation the scene describes, reading any images and SVG
+    /// files it refers to.
+    pub fn build(&self) -> Result<Simulation, SceneError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SceneError::EmptyGrid);
+        }
+        self.config.validate().map_err(SceneError::Config)?;
+        let mut sim = Simulation::new(self.width, self.height, self.config.clone());
+        for obstacle in &self.obstacles {
+            match obstacle {
+                Obstacle::Shape(shape) => sim.draw_shape(shape),
+                Obstacle::Airfoil { .. } => {
+                    if let Some(airfoil) = obstacle.airfoil() {
+                        sim.draw_shape(&airfoil?.shape());
+                    }
+                }
+                Obstacle::Svg { path, min, max } => {
+                    let path = self.directory.join(path);
+                    let svg = Svg::load(&path).map_err(|e| SceneError::Svg(path, e))?;
+                    sim.draw_shape(&svg.fit(*min, *max));
+                }
+                Obstacle::Mask { path, mode } => {
+                    sim.load_mask(&self.image(path)?, *mode);
+                }
+            }
+        }
+        for &source in &self.heat_sources {
+            sim.add_heat_source(source);
+        }
+        if let Some(map) = &self.smoke_map {
+            sim.set_smoke_sources(&self.image(&map.path)?, map.channel);
+        }
+        for channel in &self.channels {
+            sim.add_channel(channel.clone())
+                .map_err(SceneError::Config)?;
+        }
+        Ok(sim)
+    }
+
+    /// The first airfoil in `obstacles`, which the viewer lets the `[` and `]`
+    /// keys rotate.
+    pub fn airfoil(&self) -> Result<Option<Airfoil>, SceneError> {
+        self.obstacles
+            .iter()
+            .find_map(Obstacle::airfoil)
+            .transpose()
+    }
+
+    fn image(&self, path: &Path) -> Result<Image, SceneError> {
+        let path = self.directory.join(path);
+        Image::load(&path).map_err(|e| SceneError::Image(path, e))
+    }
+}
+
+impl Obstacle {
+    /// The section an `Airfoil` entry describes, or `None` for other kinds.
+    fn airfoil(&self) -> Option<Result<Airfoil, SceneError>> {
+        match self {
+            Obstacle::Airfoil {
+                naca,
+                chord,
+                position,
+                angle_of_attack_degrees,
+            } => Some(naca.parse().map_err(SceneError::Naca).map(|profile| {
+                Airfoil::new(profile, *chord, *position)
+                    .with_angle_of_attack(angle_of_attack_degrees.to_radians())
+            })),
+            _ => None,
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Obstacle, Scene, SceneError, SmokeMap};
+    use crate::airfoil::Airfoil;
+    use crate::config::{
+        Boundaries, Boundary, InflowModulation, InflowShape, MultigridCycle, PressureSolver,
+        Timestep,
+    };
+    use crate::image::{ImageChannel, MaskMode};
+    use crate::shape::Shape;
+    use crate::sim::{HeatSource, Probe, ScalarChannel, ScalarSource};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// A scene that sets at least one field of every kind.
+    fn everything() -> Scene {
+        let mut scene = Scene {
+            width: 120,
+            height: 80,
+            obstacles: vec![
+                Obstacle::Shape(Shape::Difference(
+                    Box::new(Shape::circle((30.0, 40.0), 12.0)),
+                    Box::new(Shape::Union(vec![
+                        Shape::ellipse((30.0, 40.0), (4.0, 2.0), 0.5),
+                        Shape::polygon(vec![(20.0, 30.0), (25.0, 30.0), (20.0, 35.0)]),
+                    ])),
+                )),
+                Obstacle::Shape(Shape::Segment {
+                    from: (60.0, 10.0),
+                    to: (80.0, 20.0),
+                    thickness: 3.0,
+                }),
+                Obstacle::Airfoil {
+                    naca: "23012".to_string(),
+                    chord: 30.0,
+                    position: (70.0, 50.0),
+                    angle_of_attack_degrees: -4.5,
+                },
+                Obstacle::Svg {
+                    path: PathBuf::from("outline.svg"),
+                    min: (90.0, 10.0),
+                    max: (110.0, 30.0),
+                },
+                Obstacle::Mask {
+                    path: PathBuf::from("mask.pgm"),
+                    mode: MaskMode::Opaque(0.25),
+                },
+            ],
+            heat_sources: vec![HeatSource {
+                x: 10,
+                y: 70,
+                radius: 3.5,
+                temperature: 2.0,
+            }],
+            smoke_map: Some(SmokeMap {
+                path: PathBuf::from("smoke.ppm"),
+                channel: ImageChannel::Green,
+            }),
+            channels: vec![
+                ScalarChannel::new("tracer", [0, 200, 255])
+                    .with_source(ScalarSource::Circle {
+                        x: 15,
+                        y: 60,
+                        radius: 2.0,
+                        value: 1.0,
+                    })
+                    .with_decay(0.1),
+            ],
+            probes: vec![Probe {
+                name: "wake".to_string(),
+                x: 100.5,
+                y: 40.0,
+            }],
+            ..Scene::default()
+        };
+        let config = &mut scene.config;
+        config.pressure_solver = PressureSolver::Multigrid(MultigridCycle::F);
+        config.timestep = Timestep::Adaptive {
+            cfl: 0.8,
+            max_substeps: 3,
+        };
+        config.boundaries = Boundaries {
+            top: Boundary::PressureOutlet,
+            ..Boundaries::default()
+        };
+        config.inflow_shape = InflowShape::Table(vec![(0.0, 0.5), (1.0, 1.5)]);
+        config.inflow_modulation = InflowModulation::Gust {
+            start: 1.0,
+            duration: 2.0,
+            amplitude: 0.25,
+        };
+        config.body_force = (0.5, -0.25);
+        scene
+    }
+
+    #[test]
+    fn round_trips_through_toml_and_json() {
+        let scene = everything();
+        assert_eq!(Scene::from_toml(&scene.to_toml().unwrap()).unwrap(), scene);
+        assert_eq!(Scene::from_json(&scene.to_json().unwrap()).unwrap(), scene);
+        let default = Scene::default();
+        assert_eq!(Scene::from_toml("").unwrap(), default);
+        assert_eq!(Scene::from_json("{}").unwrap(), default);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_bad_values() {
+        for text in [
+            "widht = 10",
+            "[config]\nwindsped = 1.0",
+            "[config.boundaries]\nleft = \"sticky\"",
+            "[[obstacles]]\nteapot = {}",
+            "[[channels]]\nname = \"dye\"\ncolor = [1, 2, 3]\nglow = 1.0",
+        ] {
+            assert!(
+                matches!(Scene::from_toml(text), Err(SceneError::Parse(_))),
+                "{}",
+                text
+            );
+        }
+        let scene = |text| Scene::from_toml(text).unwrap().build().map(|_| ());
+        assert!(matches!(scene("width = 0"), Err(SceneError::EmptyGrid)));
+        assert!(matches!(
+            scene("[config]\ndt = -1.0"),
+            Err(SceneError::Config(_))
+        ));
+        assert!(matches!(
+            scene(
+                "[[obstacles]]\nairfoil = { naca = \"2012\", chord = 10.0, position = [5.0, 5.0] }"
+            ),
+            Err(SceneError::Naca(_))
+        ));
+        assert!(matches!(
+            scene("[[obstacles]]\nmask = { path = \"missing.pgm\", mode = { dark = 0.5 } }"),
+            Err(SceneError::Image(..))
+        ));
+        assert!(matches!(
+            scene("[[channels]]\nname = \"dye\"\ncolor = [1, 2, 3]\ndecay = -1.0"),
+            Err(SceneError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn finds_the_first_airfoil() {
+        let airfoil = everything().airfoil().unwrap().unwrap();
+        let expected = Airfoil::new("23012".parse().unwrap(), 30.0, (70.0, 50.0))
+            .with_angle_of_attack((-4.5f64).to_radians());
+        assert_eq!(airfoil, expected);
+        assert_eq!(Scene::default().airfoil().unwrap(), None);
+    }
+
+    #[test]
+    fn bundled_scenes_build() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        for entry in fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            let scene = Scene::load(&path).unwrap();
+            let sim = scene.build().unwrap();
+            assert_eq!((sim.width(), sim.height()), (scene.width, scene.height));
+        }
+    }
+
+    /// Saves the scene next to the files it refers to, reloads it and builds
+    /// the result.
+    #[test]
+    fn loads_files_relative_to_the_scene() {
+        let directory = std::env::temp_dir().join(format!("euler-scene-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("outline.svg"),
+            r#"<svg><rect x="0" y="0" width="10" height="10"/></svg>"#,
+        )
+        .unwrap();
+        // Solid down the left half only.
+        fs::write(directory.join("mask.pgm"), "P2 2 1 1 0 1").unwrap();
+        fs::write(directory.join("smoke.ppm"), "P3 1 1 4 0 2 0").unwrap();
+
+        let mut scene = everything();
+        // The mask replaces everything before it, so move it to the front.
+        scene.obstacles.rotate_right(1);
+        scene.obstacles[0] = Obstacle::Mask {
+            path: PathBuf::from("mask.pgm"),
+            mode: MaskMode::Dark(0.5),
+        };
+        let result = scene.save(directory.join("scene.toml")).and_then(|()| {
+            let loaded = Scene::load(directory.join("scene.toml"))?;
+            assert_eq!(loaded.directory, directory);
+            assert_eq!(loaded.obstacles, scene.obstacles);
+            loaded.build()
+        });
+        let _ = fs::remove_dir_all(&directory);
+        let mut sim = result.unwrap();
+
+        assert!(sim.is_solid(20, 60) && !sim.is_solid(100, 60));
+        // The SVG square fills its box, the airfoil its chord.
+        assert!(sim.is_solid(100, 20) && !sim.is_solid(100, 35));
+        assert!(sim.is_solid(70, 50));
+        let tracer = &sim.channels()[0];
+        assert_eq!(tracer.name, "tracer");
+        assert_eq!(tracer.values()[60 * 120 + 15], 1.0);
+        // The smoke map holds every fluid cell at half density.
+        sim.step();
+        assert!((sim.probe(100.5, 40.5).smoke - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,343 @@
+//! Analytic obstacle shapes, drawn with `Simulation::draw_shape`.
+//!
+//! Coordinates are in cell units with `y` pointing down the grid: cell
+//! `(i, j)` spans `[i, i + 1] x [j, j + 1]`, so its centre is at
+//! `(i + 0.5, j + 0.5)`. Angles are in radians, turning `+x` towards `+y`.
+
+/// A solid region of the plane.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Shape {
+    Circle {
+        center: (f64, f64),
+        radius: f64,
+    },
+    Ellipse {
+        center: (f64, f64),
+        radii: (f64, f64),
+        angle: f64,
+    },
+    Rectangle {
+        center: (f64, f64),
+        size: (f64, f64),
+        angle: f64,
+    },
+    /// Closed polygon through the given vertices, filled with the even-odd
+    /// rule.
+    Polygon(Vec<(f64, f64)>),
+    /// Line segment with round caps, `thickness` across.
+    Segment {
+        from: (f64, f64),
+        to: (f64, f64),
+        thickness: f64,
+    },
+    Union(Vec<Shape>),
+    /// The first shape with the second cut out of it.
+    Difference(Box<Shape>, Box<Shape>),
+}
+
+impl Shape {
+    pub fn circle(center: (f64, f64), radius: f64) -> Self {
+        Shape::Circle { center, radius }
+    }
+
+    pub fn ellipse(center: (f64, f64), radii: (f64, f64), angle: f64) -> Self {
+        Shape::Ellipse {
+            center,
+            radii,
+            angle,
+        }
+    }
+
+    /// Axis-aligned rectangle spanning `min` to `max`.
+    pub fn rectangle(min: (f64, f64), max: (f64, f64)) -> Self {
+        Shape::Rectangle {
+            center: ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0),
+            size: (max.0 - min.0, max.1 - min.1),
+            angle: 0.0,
+        }
+    }
+
+    pub fn rotated_rectangle(center: (f64, f64), size: (f64, f64), angle: f64) -> Self {
+        Shape::Rectangle {
+            center,
+            size,
+            angle,
+        }
+    }
+
+    pub fn polygon(points: Vec<(f64, f64)>) -> Self {
+        Shape::Polygon(points)
+    }
+
+    pub fn segment(from: (f64, f64), to: (f64, f64), thickness: f64) -> Self {
+        Shape::Segment {
+            from,
+            to,
+            thickness,
+        }
+    }
+
+    pub fn union(shapes: Vec<Shape>) -> Self {
+        Shape::Union(shapes)
+    }
+
+    pub fn difference(self, cut: Shape) -> Self {
+        Shape::Difference(Box::new(self), Box::new(cut))
+    }
+
+    /// Whether the point `(x, y)` lies inside the shape.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        match self {
+            Shape::Circle { center, radius } => {
+                (x - center.0).powi(2) + (y - center.1).powi(2) < radius * radius
+            }
+            Shape::Ellipse {
+                center,
+                radii,
+                angle,
+            } => {
+                let (lx, ly) = to_local(x, y, *center, *angle);
+                (lx / radii.0).powi(2) + (ly / radii.1).powi(2) < 1.0
+            }
+            Shape::Rectangle {
+                center,
+                size,
+                angle,
+            } => {
+                let (lx, ly) = to_local(x, y, *center, *angle);
+                lx.abs() < size.0 / 2.0 && ly.abs() < size.1 / 2.0
+            }
+            Shape::Polygon(points) => {
+                let mut inside = false;
+                let mut previous = match points.last() {
+                    Some(&last) => last,
+                    None => return false,
+                };
+                for &point in points {
+                    let (a, b) = (previous, point);
+                    if (a.1 > y) != (b.1 > y) && x < a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+                        inside = !inside;
+                    }
+                    previous = point;
+                }
+                inside
+            }
+            Shape::Segment {
+                from,
+                to,
+                thickness,
+            } => {
+                let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+                let length_sq = dx * dx + dy * dy;
+                let t = if length_sq > 0.0 {
+                    (((x - from.0) * dx + (y - from.1) * dy) / length_sq).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (px, py) = (from.0 + t * dx, from.1 + t * dy);
+                (x - px).powi(2) + (y - py).powi(2) < (thickness / 2.0).powi(2)
+            }
+            Shape::Union(shapes) => shapes.iter().any(|shape| shape.contains(x, y)),
+            Shape::Difference(shape, cut) => shape.contains(x, y) && !cut.contains(x, y),
+        }
+    }
+
+    /// Box `(min_x, min_y, max_x, max_y)` containing the whole shape.
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        match self {
+            Shape::Circle { center, radius } => (
+                center.0 - radius,
+                center.1 - radius,
+                center.0 + radius,
+                center.1 + radius,
+            ),
+            Shape::Ellipse { center, radii, .. } => {
+                let r = radii.0.max(radii.1);
+                (center.0 - r, center.1 - r, center.0 + r, center.1 + r)
+            }
+            Shape::Rectangle {
+                center,
+                size,
+                angle,
+            } => {
+                let (sin, cos) = angle.sin_cos();
+                let half_x = (size.0 * cos.abs() + size.1 * sin.abs()) / 2.0;
+                let half_y = (size.0 * sin.abs() + size.1 * cos.abs()) / 2.0;
+                (
+                    center.0 - half_x,
+                    center.1 - half_y,
+                    center.0 + half_x,
+                    center.1 + half_y,
+                )
+            }
+            Shape::Polygon(points) => points.iter().fold(EMPTY, |b, &(x, y)| {
+                (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y))
+            }),
+            Shape::Segment {
+                from,
+                to,
+                thickness,
+            } => {
+                let r = thickness / 2.0;
+                (
+                    from.0.min(to.0) - r,
+                    from.1.min(to.1) - r,
+                    from.0.max(to.0) + r,
+                    from.1.max(to.1) + r,
+                )
+            }
+            Shape::Union(shapes) => shapes.iter().map(Shape::bounds).fold(EMPTY, |a, b| {
+                (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+            }),
+            Shape::Difference(shape, _) => shape.bounds(),
+        }
+    }
+}
+
+/// Bounds of a shape with no area.
+const EMPTY: (f64, f64, f64, f64) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+
+/// Coordinates of `(x, y)` in the frame of a shape centred on `center` and
+/// turned by `angle`.
+fn to_local(x: f64, y: f64, center: (f64, f64), angle: f64) -> (f64, f64) {
+    let (sin, cos) = angle.sin_cos();
+    let (dx, dy) = (x - center.0, y - center.1);
+    (dx * cos + dy * sin, dy * cos - dx * sin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shape;
+    use std::f64::consts::FRAC_PI_4;
+
+    /// Checks that every point `shape` contains on a fine grid over
+    /// `[-20, 20]^2` lies inside its bounds, and that some point does.
+    fn assert_bounded(shape: &Shape) {
+        let (min_x, min_y, max_x, max_y) = shape.bounds();
+        let mut any = false;
+        for j in -200..=200 {
+            for i in -200..=200 {
+                let (x, y) = (i as f64 * 0.1, j as f64 * 0.1);
+                if shape.contains(x, y) {
+                    any = true;
+                    assert!(
+                        min_x <= x && x <= max_x && min_y <= y && y <= max_y,
+                        "{:?} contains ({}, {}) outside its bounds",
+                        shape,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+        assert!(any, "{:?} is empty", shape);
+    }
+
+    #[test]
+    fn circles_and_ellipses() {
+        let circle = Shape::circle((1.0, 2.0), 3.0);
+        assert!(circle.contains(3.9, 2.0) && !circle.contains(4.1, 2.0));
+        assert!(!circle.contains(3.2, 4.2));
+        assert_eq!(circle.bounds(), (-2.0, -1.0, 4.0, 5.0));
+
+        // Long axis turned from x onto the diagonal.
+        let ellipse = Shape::ellipse((0.0, 0.0), (6.0, 2.0), FRAC_PI_4);
+        assert!(ellipse.contains(4.0, 4.0) && !ellipse.contains(4.0, -4.0));
+        assert!(!ellipse.contains(5.0, 0.0));
+        for shape in [circle, ellipse] {
+            assert_bounded(&shape);
+        }
+    }
+
+    #[test]
+    fn rectangles_turn_about_their_centre() {
+        let upright = Shape::rectangle((-4.0, -1.0), (4.0, 1.0));
+        assert!(upright.contains(3.9, 0.9) && !upright.contains(3.9, 1.1));
+        assert_eq!(upright.bounds(), (-4.0, -1.0, 4.0, 1.0));
+
+        let turned = Shape::rotated_rectangle((10.0, 0.0), (8.0, 2.0), FRAC_PI_4);
+        assert!(turned.contains(12.5, 2.5) && turned.contains(7.5, -2.5));
+        assert!(!turned.contains(12.5, -2.5) && !turned.contains(13.9, 0.0));
+        let (min_x, min_y, max_x, max_y) = turned.bounds();
+        let half = 5.0 * FRAC_PI_4.sin();
+        for (bound, expected) in
+            [min_x, min_y, max_x, max_y]
+                .into_iter()
+                .zip([10.0 - half, -half, 10.0 + half, half])
+        {
+            assert!(
+                (bound - expected).abs() < 1e-12,
+                "{} vs {}",
+                bound,
+                expected
+            );
+        }
+        for shape in [upright, turned] {
+            assert_bounded(&shape);
+        }
+    }
+
+    #[test]
+    fn polygons_fill_by_the_even_odd_rule() {
+        // A five-pointed star, whose centre the even-odd rule leaves open.
+        let star = Shape::polygon(
+            (0..5)
+                .map(|k| {
+                    let angle = (k * 2) as f64 * 2.0 * std::f64::consts::PI / 5.0;
+                    (10.0 * angle.sin(), -10.0 * angle.cos())
+                })
+                .collect(),
+        );
+        assert!(!star.contains(0.0, 0.0));
+        assert!(star.contains(0.0, -7.0) && !star.contains(0.0, -10.5));
+        let triangle = Shape::polygon(vec![(0.0, 0.0), (6.0, 0.0), (0.0, 3.0)]);
+        assert!(triangle.contains(1.0, 1.0) && !triangle.contains(4.0, 2.0));
+        assert_eq!(triangle.bounds(), (0.0, 0.0, 6.0, 3.0));
+        assert!(!Shape::polygon(Vec::new()).contains(0.0, 0.0));
+        for shape in [star, triangle] {
+            assert_bounded(&shape);
+        }
+    }
+
+    #[test]
+    fn segments_have_round_caps() {
+        let segment = Shape::segment((-5.0, 0.0), (5.0, 0.0), 2.0);
+        assert!(segment.contains(0.0, 0.9) && !segment.contains(0.0, 1.1));
+        assert!(segment.contains(5.9, 0.0) && !segment.contains(5.8, 0.8));
+        assert_eq!(segment.bounds(), (-6.0, -1.0, 6.0, 1.0));
+        let dot = Shape::segment((3.0, 3.0), (3.0, 3.0), 4.0);
+        assert!(dot.contains(4.9, 3.0) && !dot.contains(5.1, 3.0));
+        for shape in [segment, dot] {
+            assert_bounded(&shape);
+        }
+    }
+
+    #[test]
+    fn unions_and_differences_combine_shapes() {
+        let union = Shape::union(vec![
+            Shape::circle((-5.0, 0.0), 2.0),
+            Shape::rectangle((4.0, -1.0), (8.0, 1.0)),
+        ]);
+        assert!(union.contains(-5.0, 1.5) && union.contains(7.5, 0.5));
+        assert!(!union.contains(0.0, 0.0));
+        assert_eq!(union.bounds(), (-7.0, -2.0, 8.0, 2.0));
+
+        let ring = Shape::circle((0.0, 0.0), 5.0).difference(Shape::circle((0.0, 0.0), 3.0));
+        assert!(ring.contains(4.0, 0.0) && !ring.contains(2.0, 0.0));
+        assert!(!ring.contains(6.0, 0.0));
+        assert_eq!(ring.bounds(), (-5.0, -5.0, 5.0, 5.0));
+
+        let empty = Shape::union(Vec::new());
+        assert!(!empty.contains(0.0, 0.0));
+        let (min_x, _, max_x, _) = empty.bounds();
+        assert!(min_x > max_x);
+        for shape in [union, ring] {
+            assert_bounded(&shape);
+        }
+    }
+}
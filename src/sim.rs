@@ -1,7 +1,26 @@
-use crate::util::Array2D;
-pub(crate) const EPSILON: f64 = 0.00000000001;
-use paste::paste;
-use std::cmp::min;
+use crate::config::{DivergenceNorm, PressureSolver, SimConfig, Timestep};
+use crate::shape::Shape;
+use crate::util::{Array2D, max_or_nan, wrapped};
+use std::ops::Range;
+mod advection;
+mod boundary;
+mod cutcell;
+mod diffusion;
+mod heat;
+mod interpolation;
+mod mask;
+mod multigrid;
+mod pressure;
+mod probe;
+mod scalar;
+#[cfg(test)]
+pub(crate) mod testing;
+mod vorticity;
+pub use heat::HeatSource;
+pub use pressure::SolveStats;
+pub use probe::{Probe, ProbeSample};
+pub use scalar::{ScalarChannel, ScalarSource};
+pub const EPSILON: f64 = 0.00000000001;
 #[derive(Debug)]
 pub struct Simulation {
     width: usize,
@@ -9,136 +28,278 @@ pub struct Simulation {
     u: Array2D<f64>, // x; lie on vertical cell boundaries
     v: Array2D<f64>, // y; lie on horizontal cell boundaries,
     s: Array2D<f64>,
+    // Open fraction of each face; below one only where a cut-cell obstacle
+    // partly covers it.
+    u_aperture: Array2D<f64>,
+    v_aperture: Array2D<f64>,
     p: Array2D<f64>,
     smoke: Array2D<f64>,
+    temperature: Array2D<f64>,
+    curl: Array2D<f64>,
+    heat_sources: Vec<HeatSource>,
+    // Cells whose smoke is held at a value, from `set_smoke_sources`.
+    smoke_sources: Vec<(usize, usize, f64)>,
+    channels: Vec<ScalarChannel>,
+    config: SimConfig,
+    last_solve: SolveStats,
+    /// Simulated time since the last reset.
+    time: f64,
 }
-const DRAW_OBSTACLE: bool = true;
-const WITH_GRAVITY: bool = false;
-
-const OVERRELAXATION_FACTOR: f64 = 1.94;
-const NUM_PROJ_ITERATIONS: u32 = 100;
-const GRAVITY: f64 = 7.2;
-const DENSITY: f64 = 10.0;
-const WINDSPEED: f64 = 10.0;
-const BAND_WIDTH: usize = 5;
-const NUM_BANDS: usize = 9;
-const DT: f64 = 0.22;
-const H: f64 = 0.4;
-
-const FLUID: f64 = 1.0;
-const SOLID: f64 = -EPSILON;
-
-macro_rules! create_sample_method {
-    ($field:ident,$dx:expr, $dy:expr ) => {
-        paste! {
-            fn [<sample_ $field>](&self, x_in: f64, y_in: f64) -> f64 {
-                let x = H.max(x_in.min(self.width as f64 * H));
-                let y = H.max(y_in.min(self.height as f64 * H));
-
-                let x0 = min(((x - $dx) / H).floor() as usize, self.width - 1);
-                let tx = ((x - $dx) - x0 as f64 * H) / H;
-                let x1 = min(x0 + 1, self.width - 1);
-
-                let y0 = min(((y - $dy) / H).floor() as usize, self.height - 1);
-                let ty = ((y - $dy) - y0 as f64 * H) / H;
-                let y1 = min(y0 + 1, self.height - 1);
-
-                let sx = 1.0 - tx;
-                let sy = 1.0 - ty;
-
-                return sx * sy * self.$field[(x0, y0)]
-                    + tx * sy * self.$field[(x1, y0)]
-                    + tx * ty * self.$field[(x1, y1)]
-                    + sx * ty * self.$field[(x0, y1)];
-            }
-        }
-    };
+
+pub const FLUID: f64 = 1.0;
+pub const SOLID: f64 = -EPSILON;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DivergenceStats {
+    pub max: f64,
+    pub rms: f64,
 }
 
-impl Simulation {
-    pub fn new(width: usize, height: usize) -> Self {
-        let mut u = Array2D::new(width + 1, height);
-        for y in 0..height {
-            u[(0, y)] = WINDSPEED;
-            u[(width, y)] = WINDSPEED;
-        }
-
-        let mut s = Array2D::fill(FLUID, width, height);
-        if DRAW_OBSTACLE {
-            s.fill_circle(
-                width as i32 / 3,
-                height as i32 / 2,
-                width as f32 / 7.0,
-                SOLID,
-            );
+impl DivergenceStats {
+    /// Summarises a residual array whose non-fluid entries are zero. Any NaN
+    /// entry makes both figures NaN.
+    fn from_residual(r: &[f64], fluid_cells: usize) -> Self {
+        let max = r.iter().fold(0.0, |acc, &x| max_or_nan(acc, x.abs()));
+        let sum_sq: f64 = r.iter().map(|x| x * x).sum();
+        DivergenceStats {
+            max,
+            rms: (sum_sq / fluid_cells.max(1) as f64).sqrt(),
+        }
+    }
+
+    pub fn norm(&self, norm: DivergenceNorm) -> f64 {
+        match norm {
+            DivergenceNorm::Max => self.max,
+            DivergenceNorm::Rms => self.rms,
         }
+    }
+}
 
+/// Solver health for one call to `Simulation::step`. Divergence is measured
+/// immediately before and after the pressure projection; with substeps, the
+/// solver and divergence figures are those of the last one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StepStats {
+    pub solve: SolveStats,
+    pub divergence_before: DivergenceStats,
+    pub divergence_after: DivergenceStats,
+    /// Length of each substep.
+    pub dt: f64,
+    pub substeps: u32,
+}
+
+impl Simulation {
+    /// Creates a `width` by `height` grid at rest. `config` is used as given:
+    /// build it with `SimConfig::builder()` or check it with
+    /// `SimConfig::validate`, since out-of-range values such as a negative
+    /// `dt` make the simulation blow up rather than fail here.
+    pub fn new(width: usize, height: usize, config: SimConfig) -> Self {
         let mut smoke = Array2D::new(width, height);
-        for y in 0..NUM_BANDS {
-            let band_spacing = height / NUM_BANDS;
+        for y in 0..config.num_bands {
+            let band_spacing = height / config.num_bands;
             let center = band_spacing * y + band_spacing / 2;
-            for i in 0..BAND_WIDTH {
-                smoke[(0, center + i)] = 1.0;
-                smoke[(0, center - i)] = 1.0;
+            // Rows further than `height` from the centre are off the grid.
+            for i in 0..config.band_width.min(height) {
+                if center + i < height {
+                    smoke[(0, center + i)] = 1.0;
+                }
+                if let Some(below) = center.checked_sub(i) {
+                    smoke[(0, below)] = 1.0;
+                }
             }
         }
 
-        Simulation {
+        let mut sim = Simulation {
             width,
             height,
-            u,
+            u: Array2D::new(width + 1, height),
             v: Array2D::new(width, height + 1),
-            s,
+            s: Array2D::fill(FLUID, width, height),
+            u_aperture: Array2D::fill(1.0, width + 1, height),
+            v_aperture: Array2D::fill(1.0, width, height + 1),
             p: Array2D::new(width, height),
             smoke,
+            temperature: Array2D::fill(config.ambient_temperature, width, height),
+            curl: Array2D::new(width, height),
+            heat_sources: Vec::new(),
+            smoke_sources: Vec::new(),
+            channels: Vec::new(),
+            config,
+            last_solve: SolveStats::default(),
+            time: 0.0,
+        };
+        if sim.config.draw_obstacle {
+            sim.draw_obstacle(width as i32 / 3, height as i32 / 2, width as f32 / 7.0);
         }
+        sim.apply_boundaries(sim.config.dt);
+        sim
     }
 
     pub fn gravitation(&mut self, dt: f64) {
         for y in 0..self.height {
             for x in 0..self.width {
                 if self.open_v(x, y) {
-                    self.v[(x, y)] += GRAVITY * dt;
+                    self.v[(x, y)] += self.config.gravity * dt;
+                }
+            }
+        }
+    }
+
+    /// Accelerates every open face by `config.body_force`.
+    fn apply_body_force(&mut self, dt: f64) {
+        let (fx, fy) = self.config.body_force;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.open_u(x, y) {
+                    self.u[(x, y)] += fx * dt;
+                }
+                if self.open_v(x, y) {
+                    self.v[(x, y)] += fy * dt;
+                }
+            }
+        }
+    }
+
+    /// Sets every open face to the matching component of `velocity(x, y)`,
+    /// evaluated at the face centre in world units, to start a run from a
+    /// flowing state such as a shear layer. Inflow edges are reset by the next
+    /// step and the projection removes any divergence.
+    pub fn set_velocity(&mut self, velocity: impl Fn(f64, f64) -> (f64, f64)) {
+        let h = self.config.h;
+        for y in 0..self.height {
+            for x in 0..=self.width {
+                if self.open_u(x, y) {
+                    self.u[(x, y)] = velocity(x as f64 * h, (y as f64 + 0.5) * h).0;
+                }
+            }
+        }
+        for y in 0..=self.height {
+            for x in 0..self.width {
+                if self.open_v(x, y) {
+                    self.v[(x, y)] = velocity((x as f64 + 0.5) * h, y as f64 * h).1;
                 }
             }
         }
+        self.sync_periodic_faces();
     }
 
     fn projection(&mut self, dt: f64) {
-        self.p.zero();
-        for _ in 0..NUM_PROJ_ITERATIONS as usize {
+        self.last_solve = match self.config.pressure_solver {
+            PressureSolver::Sor => self.sor_projection(dt),
+            PressureSolver::ConjugateGradient => self.pcg_projection(dt),
+            PressureSolver::Multigrid(cycle) => self.multigrid_projection(dt, cycle),
+        };
+        self.sync_periodic_faces();
+    }
+
+    /// Net flux out of cell `(x, y)`, counting each face by its open fraction.
+    fn divergence(&self, x: usize, y: usize) -> f64 {
+        let (right, below) = (self.wrap_u(x + 1), self.wrap_v(y + 1));
+        self.u_aperture[(right, y)] * self.u[(right, y)] - self.u_aperture[(x, y)] * self.u[(x, y)]
+            + self.v_aperture[(x, below)] * self.v[(x, below)]
+            - self.v_aperture[(x, y)] * self.v[(x, y)]
+    }
+
+    /// Whether the domain wraps around in x and in y.
+    fn periodic(&self) -> (bool, bool) {
+        self.config.boundaries.periodic()
+    }
+
+    /// Index of the `u` face stored for face `x`. On an x-periodic domain the
+    /// last face is the first one seen from the other side; solvers write to
+    /// the first and `sync_periodic_faces` copies it over.
+    fn wrap_u(&self, x: usize) -> usize {
+        if x == self.width && self.periodic().0 {
+            0
+        } else {
+            x
+        }
+    }
+
+    /// Index of the `v` face stored for face `y`; see `wrap_u`.
+    fn wrap_v(&self, y: usize) -> usize {
+        if y == self.height && self.periodic().1 {
+            0
+        } else {
+            y
+        }
+    }
+
+    /// Copies the first face of each periodic axis onto the last, which
+    /// describes the same face.
+    fn sync_periodic_faces(&mut self) {
+        let (periodic_x, periodic_y) = self.periodic();
+        if periodic_x {
             for y in 0..self.height {
-                for x in 0..self.width {
-                    if self.s[(x, y)] != FLUID {
-                        continue;
-                    }
-                    let d = OVERRELAXATION_FACTOR
-                        * (self.u[(x + 1, y)] - self.u[(x, y)] + self.v[(x, y + 1)]
-                            - self.v[(x, y)]);
-                    let s1 = self.s(x as i32 - 1, y as i32);
-                    let s2 = self.s(x as i32 + 1, y as i32);
-                    let s3 = self.s(x as i32, y as i32 - 1);
-                    let s4 = self.s(x as i32, y as i32 + 1);
-                    let s = s1 + s2 + s3 + s4;
-                    if s == 0.0 {
-                        continue;
-                    }
-                    self.u[(x, y)] += d * s1 / s;
-                    self.u[(x + 1, y)] -= d * s2 / s;
-                    self.v[(x, y)] += d * s3 / s;
-                    self.v[(x, y + 1)] -= d * s4 / s;
+                self.u[(self.width, y)] = self.u[(0, y)];
+            }
+        }
+        if periodic_y {
+            for x in 0..self.width {
+                self.v[(x, self.height)] = self.v[(x, 0)];
+            }
+        }
+    }
+
+    /// Periods of the cell grid along each axis, as taken by
+    /// `Array2D::fill_circle_wrapping`.
+    fn periods(&self) -> (Option<usize>, Option<usize>) {
+        let (periodic_x, periodic_y) = self.periodic();
+        (
+            periodic_x.then_some(self.width),
+            periodic_y.then_some(self.height),
+        )
+    }
 
-                    self.p[(x, y)] -= d / s * DENSITY * H / dt;
+    fn divergence_stats(&self) -> DivergenceStats {
+        let mut max: f64 = 0.0;
+        let mut sum_sq = 0.0;
+        let mut cells = 0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.s[(x, y)] == FLUID {
+                    let d = self.divergence(x, y);
+                    max = max_or_nan(max, d.abs());
+                    sum_sq += d * d;
+                    cells += 1;
                 }
             }
         }
+        DivergenceStats {
+            max,
+            rms: if cells > 0 {
+                (sum_sq / cells as f64).sqrt()
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn fluid_cells(&self) -> usize {
+        self.s.data.iter().filter(|&&s| s == FLUID).count()
+    }
+
+    /// Statistics reported by the pressure solver during the last step.
+    pub fn solve_stats(&self) -> SolveStats {
+        self.last_solve
     }
 
     fn s(&self, x: i32, y: i32) -> f64 {
+        let (periodic_x, periodic_y) = self.periodic();
+        let x = if periodic_x {
+            x.rem_euclid(self.width as i32)
+        } else {
+            x
+        };
+        let y = if periodic_y {
+            y.rem_euclid(self.height as i32)
+        } else {
+            y
+        };
         if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
             self.s[(x as usize, y as usize)]
         } else if x == -1 || x == self.width as i32 || y == -1 || y == self.height as i32 {
-            SOLID
+            self.outside_s(x, y)
         } else {
             panic!(
                 "({},{}) not in [-1, {}]x[-1, {}]",
@@ -148,84 +309,95 @@ impl Simulation {
     }
 
     fn open_u(&self, x: usize, y: usize) -> bool {
-        self.s(x as i32, y as i32) == FLUID && self.s(x as i32 - 1, y as i32) == FLUID
+        self.s(x as i32, y as i32) == FLUID
+            && self.s(x as i32 - 1, y as i32) == FLUID
+            && self.u_aperture[(x, y)] > 0.0
     }
     fn open_v(&self, x: usize, y: usize) -> bool {
-        self.s(x as i32, y as i32) == FLUID && self.s(x as i32, y as i32 - 1) == FLUID
+        self.s(x as i32, y as i32) == FLUID
+            && self.s(x as i32, y as i32 - 1) == FLUID
+            && self.v_aperture[(x, y)] > 0.0
     }
 
     fn avg_u(&self, x: usize, y: usize) -> f64 {
-        (self.u[(x, y - 1)] + self.u[(x, y)] + self.u[(x + 1, y - 1)] + self.u[(x + 1, y)]) * 0.25
+        // Row 0 only reaches here on a y-periodic domain.
+        let below = if y == 0 { self.height - 1 } else { y - 1 };
+        (self.u[(x, below)] + self.u[(x, y)] + self.u[(x + 1, below)] + self.u[(x + 1, y)]) * 0.25
     }
     fn avg_v(&self, x: usize, y: usize) -> f64 {
-        (self.v[(x - 1, y)] + self.v[(x, y)] + self.v[(x - 1, y + 1)] + self.v[(x, y + 1)]) * 0.25
-    }
-
-    create_sample_method!(u, 0.0, H / 2.0);
-    create_sample_method!(v, H / 2.0, 0.0);
-    create_sample_method!(smoke, H / 2.0, H / 2.0);
-
-    pub fn step(&mut self) {
-        if WITH_GRAVITY {
-            self.gravitation(DT);
-        }
-        self.projection(DT);
-        self.advection(DT);
-        self.smoke_advection(DT);
+        // Column 0 only reaches here on an x-periodic domain.
+        let left = if x == 0 { self.width - 1 } else { x - 1 };
+        (self.v[(left, y)] + self.v[(x, y)] + self.v[(left, y + 1)] + self.v[(x, y + 1)]) * 0.25
     }
 
-    fn advection(&mut self, dt: f64) {
-        let mut new_u = self.u.clone();
-        let mut new_v = self.v.clone();
-        for j in 0..=self.height {
-            for i in 0..=self.width {
-                if (1..self.width).contains(&i) && self.open_u(i, j) {
-                    let mut x = i as f64 * H;
-                    let mut y = j as f64 * H + 0.5 * H;
-                    let u = new_u[(i, j)];
-                    let v = self.avg_v(i, j);
-
-                    x -= dt * u;
-                    y -= dt * v;
-
-                    new_u[(i, j)] = self.sample_u(x, y);
-                }
-
-                if (1..self.height).contains(&j) && self.open_v(i, j) {
-                    let mut x = i as f64 * H + 0.5 * H;
-                    let mut y = j as f64 * H;
-                    let v = new_v[(i, j)];
-                    let u = self.avg_u(i, j);
-
-                    x -= dt * u;
-                    y -= dt * v;
-
-                    new_v[(i, j)] = self.sample_v(x, y);
-                }
+    /// Advances the simulation by `dt`, in one go or in CFL-limited substeps
+    /// depending on the configured `Timestep`.
+    pub fn step(&mut self) -> StepStats {
+        let substeps = match self.config.timestep {
+            Timestep::Fixed => 1,
+            Timestep::Adaptive { cfl, max_substeps } => {
+                let cells = self.config.dt * self.max_velocity() / self.config.h;
+                ((cells / cfl).ceil() as u32).clamp(1, max_substeps)
             }
+        };
+        let dt = self.config.dt / substeps as f64;
+        let mut stats = StepStats::default();
+        for _ in 0..substeps {
+            stats = self.substep(dt);
+        }
+        StepStats {
+            dt,
+            substeps,
+            ..stats
         }
-        self.u = new_u;
-        self.v = new_v;
     }
 
-    fn smoke_advection(&mut self, dt: f64) {
-        let mut new_smoke = self.smoke.clone();
-        for j in 1..self.height {
-            for i in 1..self.width {
-                if self.s[(i, j)] != FLUID {
-                    continue;
-                }
-
-                let u = 0.5 * (self.u[(i, j)] + self.u[(i + 1, j)]);
-                let v = 0.5 * (self.v[(i, j)] + self.v[(i, j + 1)]);
-
-                let x = i as f64 * H + 0.5 * H - dt * u;
-                let y = j as f64 * H + 0.5 * H - dt * v;
+    /// Largest velocity component anywhere on the grid, or NaN if any
+    /// component is NaN.
+    pub fn max_velocity(&self) -> f64 {
+        self.u
+            .data
+            .iter()
+            .chain(&self.v.data)
+            .fold(0.0, |acc, &x| max_or_nan(acc, x.abs()))
+    }
 
-                new_smoke[(i, j)] = self.sample_smoke(x, y);
-            }
+    fn substep(&mut self, dt: f64) -> StepStats {
+        self.apply_boundaries(dt);
+        if self.config.with_gravity {
+            self.gravitation(dt);
+        }
+        if self.config.body_force != (0.0, 0.0) {
+            self.apply_body_force(dt);
+        }
+        self.apply_heat_sources();
+        self.apply_smoke_sources();
+        if self.config.smoke_weight != 0.0 || self.config.thermal_expansion != 0.0 {
+            self.buoyancy(dt);
+        }
+        self.compute_curl();
+        if self.config.vorticity_confinement > 0.0 {
+            self.vorticity_confinement(dt);
+        }
+        if self.config.viscosity > 0.0 {
+            self.viscosity(dt);
+        }
+        let divergence_before = self.divergence_stats();
+        self.projection(dt);
+        let divergence_after = self.divergence_stats();
+        self.advection(dt);
+        self.smoke_advection(dt);
+        self.temperature_advection(dt);
+        self.channel_advection(dt);
+        self.sync_periodic_faces();
+        self.time += dt;
+        StepStats {
+            solve: self.last_solve,
+            divergence_before,
+            divergence_after,
+            dt,
+            substeps: 1,
         }
-        self.smoke = new_smoke;
     }
 
     pub fn cell_info(&self, x: usize, y: usize) {
@@ -235,6 +407,8 @@ impl Simulation {
         println!("s: {:.5}", self.s[(x, y)]);
         println!("p: {:.5}", self.p[(x, y)]);
         println!("smoke: {:.5}", self.smoke[(x, y)]);
+        println!("temperature: {:.5}", self.temperature[(x, y)]);
+        println!("curl: {:.5}", self.curl[(x, y)]);
     }
 
     pub fn get_speed(&self) -> Vec<f64> {
@@ -253,29 +427,449 @@ impl Simulation {
         &self.smoke.data
     }
 
+    pub fn get_temperature(&self) -> &[f64] {
+        &self.temperature.data
+    }
+
     pub fn get_pressure(&self) -> &[f64] {
         &self.p.data
     }
 
+    /// Vorticity at each cell centre, computed at the start of the last step.
+    pub fn get_curl(&self) -> &[f64] {
+        &self.curl.data
+    }
+
     pub fn get_s(&self) -> &[f64] {
         &self.s.data
     }
 
+    /// Horizontal face velocities, `(width + 1) x height` in row-major order.
+    pub fn get_u(&self) -> &[f64] {
+        &self.u.data
+    }
+
+    /// Vertical face velocities, `width x (height + 1)` in row-major order.
+    pub fn get_v(&self) -> &[f64] {
+        &self.v.data
+    }
+
+    /// Simulated time since the simulation was created or last reset.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn config(&self) -> &SimConfig {
+        &self.config
+    }
+
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        self.s[(x, y)] != FLUID
+    }
+
+    /// Marks a single cell as solid or fluid, zeroing its pressure, smoke and
+    /// face velocities when it becomes solid. When it becomes fluid, faces a
+    /// cut-cell obstacle closed reopen once the cell across them is fluid too.
+    pub fn set_solid(&mut self, x: usize, y: usize, solid: bool) {
+        if !solid {
+            self.s[(x, y)] = FLUID;
+            self.reopen_faces(x, y);
+            return;
+        }
+        self.s[(x, y)] = SOLID;
+        self.p[(x, y)] = 0.0;
+        self.smoke[(x, y)] = 0.0;
+        self.temperature[(x, y)] = self.config.ambient_temperature;
+        self.clear_channels_in(x as i32, y as i32, 0.0);
+        self.u[(x, y)] = 0.0;
+        self.u[(x + 1, y)] = 0.0;
+        self.v[(x, y)] = 0.0;
+        self.v[(x, y + 1)] = 0.0;
+    }
+
+    /// Fully opens the closed faces of cell `(x, y)` that now separate two
+    /// fluid cells. A face next to a solid cell stays shut until that cell is
+    /// cleared as well, and a partly open face keeps its aperture, since the
+    /// obstacle that narrowed it may still be there.
+    fn reopen_faces(&mut self, x: usize, y: usize) {
+        let (i, j) = (x as i32, y as i32);
+        let (periodic_x, periodic_y) = self.periodic();
+        for (face, neighbour) in [(x, i - 1), (x + 1, i + 1)] {
+            if self.s(neighbour, j) == FLUID && self.u_aperture[(face, y)] == 0.0 {
+                self.u_aperture[(face, y)] = 1.0;
+                if periodic_x && (face == 0 || face == self.width) {
+                    self.u_aperture[(self.width - face, y)] = 1.0;
+                }
+            }
+        }
+        for (face, neighbour) in [(y, j - 1), (y + 1, j + 1)] {
+            if self.s(i, neighbour) == FLUID && self.v_aperture[(x, face)] == 0.0 {
+                self.v_aperture[(x, face)] = 1.0;
+                if periodic_y && (face == 0 || face == self.height) {
+                    self.v_aperture[(x, self.height - face)] = 1.0;
+                }
+            }
+        }
+    }
+
+    /// Removes every obstacle, including the one placed by `new`.
+    pub fn clear_obstacles(&mut self) {
+        self.s.reset(FLUID);
+        self.u_aperture.reset(1.0);
+        self.v_aperture.reset(1.0);
+    }
+
+    /// Draws a solid disc centred on cell `(center_x, center_y)`, wrapping
+    /// across periodic edges. With `cut_cells` the disc covers cells and faces
+    /// fractionally.
     pub fn draw_obstacle(&mut self, center_x: i32, center_y: i32, radius: f32) {
-        self.s.fill_circle(center_x, center_y, radius, SOLID);
-        self.p.fill_circle(center_x, center_y, radius, 0.0);
-        self.smoke.fill_circle(center_x, center_y, radius, 0.0);
-        self.u.fill_circle(center_x, center_y, radius + 1.0, 0.0);
-        self.v.fill_circle(center_x, center_y, radius + 1.0, 0.0);
+        if self.config.cut_cells {
+            let center = (center_x as f64 + 0.5, center_y as f64 + 0.5);
+            self.draw_shape(&Shape::circle(center, radius as f64));
+            return;
+        }
+        let period = self.periods();
+        self.s
+            .fill_circle_wrapping(center_x, center_y, radius, SOLID, period);
+        self.p
+            .fill_circle_wrapping(center_x, center_y, radius, 0.0, period);
+        self.smoke
+            .fill_circle_wrapping(center_x, center_y, radius, 0.0, period);
+        self.temperature.fill_circle_wrapping(
+            center_x,
+            center_y,
+            radius,
+            self.config.ambient_temperature,
+            period,
+        );
+        self.clear_channels_in(center_x, center_y, radius);
+        self.u
+            .fill_circle_wrapping(center_x, center_y, radius + 1.0, 0.0, period);
+        self.v
+            .fill_circle_wrapping(center_x, center_y, radius + 1.0, 0.0, period);
+    }
+
+    /// Adds `shape` to the obstacles, wrapping across periodic edges. Cells
+    /// whose centre lies inside become solid; with `cut_cells` the shape covers
+    /// cells and faces fractionally instead.
+    pub fn draw_shape(&mut self, shape: &Shape) {
+        let (xs, ys) = self.cells_under(shape);
+        if self.config.cut_cells {
+            self.cover(xs, ys, |x, y| shape.contains(x, y));
+            return;
+        }
+        self.for_cells_inside(shape, xs, ys, |sim, x, y| sim.set_solid(x, y, true));
+    }
+
+    /// Turns the cells whose centre lies inside `shape` back into fluid. With
+    /// `cut_cells` this undoes `draw_shape`, reopening the part of each face
+    /// the shape covers.
+    pub fn erase_shape(&mut self, shape: &Shape) {
+        let (xs, ys) = self.cells_under(shape);
+        if self.config.cut_cells {
+            self.uncover(xs, ys, |x, y| shape.contains(x, y));
+            return;
+        }
+        self.for_cells_inside(shape, xs, ys, |sim, x, y| sim.set_solid(x, y, false));
+    }
+
+    /// Cell ranges covering the bounds of `shape`, clipped to the grid along
+    /// axes that do not wrap and to one period along those that do.
+    fn cells_under(&self, shape: &Shape) -> (Range<i32>, Range<i32>) {
+        let (min_x, min_y, max_x, max_y) = shape.bounds();
+        let (periodic_x, periodic_y) = self.periodic();
+        let range = |min: f64, max: f64, len: usize, periodic: bool| {
+            let len = len as i32;
+            if min > max || min.is_nan() || max.is_nan() {
+                return 0..0;
+            }
+            let (start, end) = (min.floor() as i32, max.ceil() as i32);
+            if periodic {
+                start..end.min(start.saturating_add(len))
+            } else {
+                start.max(0)..end.min(len)
+            }
+        };
+        (
+            range(min_x, max_x, self.width, periodic_x),
+            range(min_y, max_y, self.height, periodic_y),
+        )
     }
 
+    /// Calls `f` on every cell in `xs` by `ys` whose centre lies inside
+    /// `shape`, wrapping indices across periodic edges.
+    fn for_cells_inside(
+        &mut self,
+        shape: &Shape,
+        xs: Range<i32>,
+        ys: Range<i32>,
+        mut f: impl FnMut(&mut Self, usize, usize),
+    ) {
+        let period = self.periods();
+        for j in ys {
+            for i in xs.clone() {
+                if !shape.contains(i as f64 + 0.5, j as f64 + 0.5) {
+                    continue;
+                }
+                for x in wrapped(i, self.width, period.0) {
+                    for y in wrapped(j, self.height, period.1) {
+                        f(self, x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn add_heat_source(&mut self, source: HeatSource) {
+        self.heat_sources.push(source);
+    }
+
+    pub fn clear_heat_sources(&mut self) {
+        self.heat_sources.clear();
+    }
+
+    pub fn heat_sources(&self) -> &[HeatSource] {
+        &self.heat_sources
+    }
+
+    /// Restarts the flow but keeps obstacles, heat and smoke sources and
+    /// scalar channels, whose contents are emptied.
     pub fn reset_except_walls(&mut self) {
         let old_s = self.s.clone();
+        let u_aperture = self.u_aperture.clone();
+        let v_aperture = self.v_aperture.clone();
+        let heat_sources = std::mem::take(&mut self.heat_sources);
+        let smoke_sources = std::mem::take(&mut self.smoke_sources);
+        let channels = std::mem::take(&mut self.channels);
         self.reset();
         self.s = old_s;
+        self.u_aperture = u_aperture;
+        self.v_aperture = v_aperture;
+        self.heat_sources = heat_sources;
+        self.smoke_sources = smoke_sources;
+        for channel in channels {
+            self.insert_channel(channel);
+        }
     }
 
     pub fn reset(&mut self) {
-        *self = Self::new(self.width, self.height);
+        *self = Self::new(self.width, self.height, self.config.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::unstable_config;
+    use super::{DivergenceStats, Simulation};
+    use crate::config::{Boundaries, Boundary, ConfigError, Interpolation, SimConfig, Timestep};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn residual_stats_propagate_nan() {
+        let stats = DivergenceStats::from_residual(&[0.5, f64::NAN, -2.0], 3);
+        assert!(stats.max.is_nan() && stats.rms.is_nan());
+        let stats = DivergenceStats::from_residual(&[0.5, -2.0], 2);
+        assert_eq!(stats.max, 2.0);
+    }
+
+    #[test]
+    fn blow_up_is_reported() {
+        let mut sim = Simulation::new(40, 30, unstable_config());
+        let blown_up = (0..60).any(|_| !sim.step().divergence_after.max.is_finite());
+        assert!(blown_up, "divergence stayed finite");
+        assert!(!sim.divergence_stats().max.is_finite());
+        assert!(!sim.max_velocity().is_finite());
+    }
+
+    #[test]
+    fn adaptive_substeps_keep_to_the_cfl_target() {
+        for max_substeps in [50, 4] {
+            let config = SimConfig::builder()
+                .windspeed(30.0)
+                .timestep(Timestep::Adaptive {
+                    cfl: 1.0,
+                    max_substeps,
+                })
+                .build()
+                .unwrap();
+            let (frame, h) = (config.dt, config.h);
+            let mut sim = Simulation::new(20, 15, config);
+            sim.step();
+            for _ in 0..2 {
+                let (speed, start) = (sim.max_velocity(), sim.time());
+                let stats = sim.step();
+                assert!(stats.substeps > 1 && stats.substeps <= max_substeps);
+                assert!((stats.dt * stats.substeps as f64 - frame).abs() < 1e-12);
+                assert!((sim.time() - start - frame).abs() < 1e-9);
+                if max_substeps == 4 {
+                    // Too fast for four substeps, so the CFL target is given up.
+                    assert_eq!(stats.substeps, 4);
+                } else {
+                    assert!(speed * stats.dt / h <= 1.0, "{:?}", stats);
+                    // No more substeps than the target needs.
+                    let longer = frame / (stats.substeps - 1) as f64;
+                    assert!(speed * longer / h > 1.0, "{:?}", stats);
+                }
+            }
+        }
+    }
+
+    const CHANNEL: Boundaries = Boundaries {
+        left: Boundary::Periodic,
+        right: Boundary::Periodic,
+        top: Boundary::FreeSlip,
+        bottom: Boundary::FreeSlip,
+    };
+
+    #[test]
+    fn body_force_drives_a_periodic_channel() {
+        let config = SimConfig::builder()
+            .boundaries(CHANNEL)
+            .draw_obstacle(false)
+            .body_force((2.0, 0.0))
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(24, 16, config);
+        for _ in 0..5 {
+            sim.step();
+        }
+        let expected = 2.0 * 5.0 * sim.config.dt;
+        assert!(sim.u.data.iter().all(|u| (u - expected).abs() < 1e-9));
+        assert!(sim.v.data.iter().all(|v| v.abs() < 1e-9));
+    }
+
+    #[test]
+    fn set_velocity_fills_open_faces() {
+        let config = SimConfig::builder().boundaries(CHANNEL).build().unwrap();
+        let mut sim = Simulation::new(24, 16, config);
+        let depth = 16.0 * sim.config.h;
+        sim.set_velocity(|_, y| ((2.0 * PI * y / depth).sin(), 0.0));
+        for y in 0..sim.height {
+            let expected = (2.0 * PI * (y as f64 + 0.5) / 16.0).sin();
+            for x in 0..=sim.width {
+                // Faces inside the obstacle keep the fluid at rest.
+                let expected = if sim.open_u(x, y) { expected } else { 0.0 };
+                assert!((sim.u[(x, y)] - expected).abs() < 1e-12, "({}, {})", x, y);
+            }
+        }
+    }
+
+    const TORUS: Boundaries = Boundaries {
+        left: Boundary::Periodic,
+        right: Boundary::Periodic,
+        top: Boundary::Periodic,
+        bottom: Boundary::Periodic,
+    };
+
+    /// A 30 by 20 torus around the default obstacle, started from a pair of
+    /// crossing shear layers.
+    fn swirling_torus(interpolation: Interpolation) -> Simulation {
+        let config = SimConfig::builder()
+            .boundaries(TORUS)
+            .interpolation(interpolation)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(30, 20, config);
+        let (width, height) = (30.0 * sim.config.h, 20.0 * sim.config.h);
+        sim.set_velocity(|x, y| {
+            (
+                (2.0 * PI * y / height).sin(),
+                0.5 * (2.0 * PI * x / width).cos(),
+            )
+        });
+        sim
+    }
+
+    #[test]
+    fn periodic_boundaries_come_in_pairs() {
+        let one_sided = Boundaries {
+            right: Boundary::Periodic,
+            ..Boundaries::default()
+        };
+        assert!(matches!(
+            SimConfig::builder().boundaries(one_sided).build(),
+            Err(ConfigError::Inconsistent { .. })
+        ));
+    }
+
+    #[test]
+    fn periodic_faces_stay_in_sync() {
+        let mut sim = swirling_torus(Interpolation::Bilinear);
+        for _ in 0..5 {
+            sim.step();
+            for y in 0..sim.height {
+                assert_eq!(sim.u[(sim.width, y)], sim.u[(0, y)]);
+            }
+            for x in 0..sim.width {
+                assert_eq!(sim.v[(x, sim.height)], sim.v[(x, 0)]);
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_wraps_around_periodic_axes() {
+        for interpolation in [
+            Interpolation::Bilinear,
+            Interpolation::CatmullRom,
+            Interpolation::MonotoneCubic,
+        ] {
+            let mut sim = swirling_torus(interpolation);
+            sim.step();
+            let h = sim.config.h;
+            let (width, height) = (sim.width as f64 * h, sim.height as f64 * h);
+            for (x, y) in [
+                (0.1 * h, 0.2 * h),
+                (0.7 * h, 10.5 * h),
+                (29.9 * h, 19.6 * h),
+            ] {
+                let (u, v) = sim.sample_velocity(x, y);
+                for (sx, sy) in [
+                    (x + width, y),
+                    (x - width, y),
+                    (x, y + height),
+                    (x, y - height),
+                ] {
+                    let (su, sv) = sim.sample_velocity(sx, sy);
+                    assert!(
+                        (u - su).abs() < 1e-9 && (v - sv).abs() < 1e-9,
+                        "{:?} ({}, {})",
+                        interpolation,
+                        sx,
+                        sy
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smoke_leaving_a_periodic_edge_reenters_opposite() {
+        let config = SimConfig::builder()
+            .boundaries(CHANNEL)
+            .draw_obstacle(false)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(24, 16, config);
+        // Exactly one cell per step.
+        let speed = sim.config.h / sim.config.dt;
+        sim.set_velocity(|_, _| (speed, 0.0));
+        sim.smoke.zero();
+        for y in 0..sim.height {
+            sim.smoke[(sim.width - 1, y)] = 1.0;
+        }
+        sim.step();
+        // Row 0 lies along the top wall, which advection leaves alone.
+        for y in 1..sim.height {
+            assert!((sim.smoke[(0, y)] - 1.0).abs() < 1e-9, "row {}", y);
+            assert!(sim.smoke[(sim.width - 1, y)].abs() < 1e-9, "row {}", y);
+        }
     }
 }
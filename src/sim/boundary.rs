@@ -0,0 +1,237 @@
+use super::{FLUID, SOLID, Simulation};
+use crate::config::Boundary;
+
+/// One side of the domain. `Top` is the `y = 0` row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+const EDGES: [Edge; 4] = [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom];
+
+impl Simulation {
+    /// Sets the normal velocity on every face of the domain edge from its
+    /// boundary condition. Cells outside the grid stay solid to the pressure
+    /// solver, so these faces act as prescribed fluxes; pressure outlets are
+    /// instead solved for by the projection, and periodic edges are ordinary
+    /// interior faces that only have their duplicate copy refreshed.
+    pub(super) fn apply_boundaries(&mut self, dt: f64) {
+        let modulation = self.config.inflow_modulation.factor(self.time);
+        for edge in EDGES {
+            let boundary = self.boundary(edge);
+            let blend = match boundary {
+                Boundary::Convective => (dt * self.convective_speed(edge) / self.config.h).min(1.0),
+                _ => 0.0,
+            };
+            for k in 0..self.edge_len(edge) {
+                let (current, interior) = self.edge_face(edge, k);
+                let outward = match boundary {
+                    Boundary::NoSlip | Boundary::FreeSlip => 0.0,
+                    Boundary::Inflow => {
+                        let speed = modulation * self.inflow_speed(edge, k);
+                        match edge {
+                            Edge::Left | Edge::Top => -speed,
+                            Edge::Right | Edge::Bottom => speed,
+                        }
+                    }
+                    Boundary::Outflow | Boundary::PressureOutlet => interior,
+                    Boundary::Convective => current - blend * (current - interior),
+                    Boundary::Periodic => continue,
+                };
+                self.set_edge_face(edge, k, outward);
+            }
+        }
+        self.balance_outflow();
+        self.sync_periodic_faces();
+    }
+
+    /// Value of `s` for a cell just outside the grid, which is fluid at zero
+    /// pressure beyond a pressure outlet and solid otherwise.
+    pub(super) fn outside_s(&self, x: i32, y: i32) -> f64 {
+        let edge = if x < 0 {
+            Edge::Left
+        } else if x >= self.width as i32 {
+            Edge::Right
+        } else if y < 0 {
+            Edge::Top
+        } else {
+            Edge::Bottom
+        };
+        if self.boundary(edge) == Boundary::PressureOutlet {
+            FLUID
+        } else {
+            SOLID
+        }
+    }
+
+    fn boundary(&self, edge: Edge) -> Boundary {
+        let boundaries = &self.config.boundaries;
+        match edge {
+            Edge::Left => boundaries.left,
+            Edge::Right => boundaries.right,
+            Edge::Top => boundaries.top,
+            Edge::Bottom => boundaries.bottom,
+        }
+    }
+
+    fn edge_len(&self, edge: Edge) -> usize {
+        match edge {
+            Edge::Left | Edge::Right => self.height,
+            Edge::Top | Edge::Bottom => self.width,
+        }
+    }
+
+    /// Outward normal velocity on face `k` of `edge` and on the first interior
+    /// face behind it.
+    fn edge_face(&self, edge: Edge, k: usize) -> (f64, f64) {
+        let (w, h) = (self.width, self.height);
+        match edge {
+            Edge::Left => (-self.u[(0, k)], -self.u[(1, k)]),
+            Edge::Right => (self.u[(w, k)], self.u[(w - 1, k)]),
+            Edge::Top => (-self.v[(k, 0)], -self.v[(k, 1)]),
+            Edge::Bottom => (self.v[(k, h)], self.v[(k, h - 1)]),
+        }
+    }
+
+    fn set_edge_face(&mut self, edge: Edge, k: usize, outward: f64) {
+        let (w, h) = (self.width, self.height);
+        match edge {
+            Edge::Left => self.u[(0, k)] = -outward,
+            Edge::Right => self.u[(w, k)] = outward,
+            Edge::Top => self.v[(k, 0)] = -outward,
+            Edge::Bottom => self.v[(k, h)] = outward,
+        }
+    }
+
+    /// Steady inflow speed through face `k` of `edge`, from `windspeed` and
+    /// the inflow shape.
+    fn inflow_speed(&self, edge: Edge, k: usize) -> f64 {
+        let len = self.edge_len(edge);
+        let position = (k as f64 + 0.5) / len as f64;
+        let shape = &self.config.inflow_shape;
+        self.config.windspeed * shape.factor(position, len as f64 * self.config.h)
+    }
+
+    /// Whether the cell inside face `k` of `edge` is fluid.
+    fn edge_cell_is_fluid(&self, edge: Edge, k: usize) -> bool {
+        let (w, h) = (self.width, self.height);
+        let cell = match edge {
+            Edge::Left => (0, k),
+            Edge::Right => (w - 1, k),
+            Edge::Top => (k, 0),
+            Edge::Bottom => (k, h - 1),
+        };
+        self.s[cell] == FLUID
+    }
+
+    /// Mean outward velocity through the open faces of `edge`, ignoring
+    /// backflow; the speed at which a convective outflow carries structures
+    /// out of the domain.
+    fn convective_speed(&self, edge: Edge) -> f64 {
+        let (sum, faces) = (0..self.edge_len(edge))
+            .filter(|&k| self.edge_cell_is_fluid(edge, k))
+            .fold((0.0, 0usize), |(sum, faces), k| {
+                (sum + self.edge_face(edge, k).0, faces + 1)
+            });
+        if faces == 0 {
+            0.0
+        } else {
+            (sum / faces as f64).max(0.0)
+        }
+    }
+
+    /// Shifts the outflow faces by a common amount so the net flux out of the
+    /// domain is zero; otherwise no pressure field can remove the divergence.
+    /// A pressure outlet absorbs any imbalance itself, so nothing is shifted.
+    fn balance_outflow(&mut self) {
+        let adjustable = |boundary| matches!(boundary, Boundary::Outflow | Boundary::Convective);
+        if EDGES
+            .iter()
+            .any(|&edge| self.boundary(edge) == Boundary::PressureOutlet)
+        {
+            return;
+        }
+
+        // Whatever leaves one periodic edge enters through the other, so
+        // those edges are left out.
+        let mut net = 0.0;
+        let mut faces = 0usize;
+        for edge in EDGES {
+            let boundary = self.boundary(edge);
+            if boundary == Boundary::Periodic {
+                continue;
+            }
+            for k in 0..self.edge_len(edge) {
+                if self.edge_cell_is_fluid(edge, k) {
+                    net += self.edge_face(edge, k).0;
+                    faces += adjustable(boundary) as usize;
+                }
+            }
+        }
+        if faces == 0 {
+            return;
+        }
+
+        let shift = -net / faces as f64;
+        for edge in EDGES {
+            if !adjustable(self.boundary(edge)) {
+                continue;
+            }
+            for k in 0..self.edge_len(edge) {
+                if self.edge_cell_is_fluid(edge, k) {
+                    let outward = self.edge_face(edge, k).0 + shift;
+                    self.set_edge_face(edge, k, outward);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Boundaries, Boundary, SimConfig};
+    use crate::sim::Simulation;
+
+    /// An inviscid tunnel around the default obstacle with `walls` at the top
+    /// and bottom, after a few steps.
+    fn tunnel(walls: Boundary) -> Simulation {
+        let config = SimConfig::builder()
+            .boundaries(Boundaries {
+                top: walls,
+                bottom: walls,
+                ..Boundaries::default()
+            })
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(40, 20, config);
+        for _ in 0..20 {
+            sim.step();
+        }
+        sim
+    }
+
+    /// Mean speed along the first and last rows of `u` faces.
+    fn wall_speed(sim: &Simulation) -> f64 {
+        let rows = [0, sim.height - 1];
+        let sum: f64 = (1..sim.width)
+            .flat_map(|x| rows.map(|y| sim.u[(x, y)].abs()))
+            .sum();
+        sum / (2 * (sim.width - 1)) as f64
+    }
+
+    #[test]
+    fn no_slip_walls_stop_the_tangential_flow_without_viscosity() {
+        let (no_slip, free_slip) = (tunnel(Boundary::NoSlip), tunnel(Boundary::FreeSlip));
+        let wall = 20.0 * no_slip.config.h;
+        for x in 1..40 {
+            let x = (x as f64 + 0.3) * no_slip.config.h;
+            assert_eq!(no_slip.sample_u(x, 0.0), 0.0);
+            assert_eq!(no_slip.sample_u(x, wall), 0.0);
+            assert!(free_slip.sample_u(x, 0.0) > 1.0);
+        }
+        assert!(wall_speed(&no_slip) < 0.75 * wall_speed(&free_slip));
+    }
+}
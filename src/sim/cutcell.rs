@@ -0,0 +1,202 @@
+use super::{FLUID, Simulation};
+use crate::util::wrapped;
+use std::ops::Range;
+
+/// Samples along each side of a cell when estimating how much of it a shape
+/// covers.
+const SAMPLES: usize = 8;
+
+impl Simulation {
+    /// Covers part of the domain with a solid described by `inside`, a test on
+    /// points in cell units where cell `(i, j)` spans `[i, i + 1] x [j, j + 1]`.
+    /// Only cells in `xs` by `ys` are visited; the ranges may reach past a
+    /// periodic edge. Cells the solid covers entirely become solid, and every
+    /// face keeps the fraction of its length that lies outside the solid, or
+    /// less where an earlier obstacle already closed more of it.
+    pub(super) fn cover(
+        &mut self,
+        xs: Range<i32>,
+        ys: Range<i32>,
+        inside: impl Fn(f64, f64) -> bool,
+    ) {
+        self.set_coverage(xs, ys, inside, true);
+    }
+
+    /// Undoes `cover` for the same solid: cells it covers entirely become
+    /// fluid and every face gets back the fraction of its length that lies
+    /// inside the solid. Erasing a shape that was drawn on its own therefore
+    /// restores the grid exactly; where obstacles overlapped, the shared part
+    /// of a face reopens with it.
+    pub(super) fn uncover(
+        &mut self,
+        xs: Range<i32>,
+        ys: Range<i32>,
+        inside: impl Fn(f64, f64) -> bool,
+    ) {
+        self.set_coverage(xs, ys, inside, false);
+    }
+
+    fn set_coverage(
+        &mut self,
+        xs: Range<i32>,
+        ys: Range<i32>,
+        inside: impl Fn(f64, f64) -> bool,
+        solid: bool,
+    ) {
+        let period = self.periods();
+        let offsets: Vec<f64> = (0..SAMPLES)
+            .map(|k| (k as f64 + 0.5) / SAMPLES as f64)
+            .collect();
+        let aperture = |current: f64, open: f64| {
+            if solid {
+                current.min(open)
+            } else {
+                (current + 1.0 - open).min(1.0)
+            }
+        };
+
+        for j in ys.clone() {
+            for i in xs.clone() {
+                let covered = offsets.iter().all(|&dy| {
+                    offsets
+                        .iter()
+                        .all(|&dx| inside(i as f64 + dx, j as f64 + dy))
+                });
+                if !covered {
+                    continue;
+                }
+                for x in wrapped(i, self.width, period.0) {
+                    for y in wrapped(j, self.height, period.1) {
+                        if solid {
+                            self.set_solid(x, y, true);
+                        } else {
+                            self.s[(x, y)] = FLUID;
+                        }
+                    }
+                }
+            }
+        }
+
+        for j in ys.clone() {
+            for i in xs.start..=xs.end {
+                let fraction =
+                    open_fraction(offsets.iter().map(|&d| inside(i as f64, j as f64 + d)));
+                for x in wrapped(i, self.width + 1, period.0) {
+                    for y in wrapped(j, self.height, period.1) {
+                        let aperture = aperture(self.u_aperture[(x, y)], fraction);
+                        self.u_aperture[(x, y)] = aperture;
+                        if aperture == 0.0 {
+                            self.u[(x, y)] = 0.0;
+                        }
+                    }
+                }
+            }
+        }
+        for j in ys.start..=ys.end {
+            for i in xs.clone() {
+                let fraction =
+                    open_fraction(offsets.iter().map(|&d| inside(i as f64 + d, j as f64)));
+                for x in wrapped(i, self.width, period.0) {
+                    for y in wrapped(j, self.height + 1, period.1) {
+                        let aperture = aperture(self.v_aperture[(x, y)], fraction);
+                        self.v_aperture[(x, y)] = aperture;
+                        if aperture == 0.0 {
+                            self.v[(x, y)] = 0.0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Fraction of the samples along a face that lie outside the solid.
+fn open_fraction(inside: impl Iterator<Item = bool>) -> f64 {
+    inside.filter(|&inside| !inside).count() as f64 / SAMPLES as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SAMPLES;
+    use crate::config::SimConfig;
+    use crate::shape::Shape;
+    use crate::sim::Simulation;
+
+    fn cut_cell_sim(width: usize, height: usize) -> Simulation {
+        let config = SimConfig::builder()
+            .cut_cells(true)
+            .draw_obstacle(false)
+            .build()
+            .unwrap();
+        Simulation::new(width, height, config)
+    }
+
+    /// Length of `[start, start + 1]` that lies outside `[-half, half]`
+    /// around `center`.
+    fn open_length(start: f64, center: f64, half: f64) -> f64 {
+        let covered = (start + 1.0).min(center + half) - start.max(center - half);
+        1.0 - covered.max(0.0)
+    }
+
+    #[test]
+    fn apertures_match_the_open_length_of_a_circle() {
+        let (cx, cy, radius) = (12.5, 11.5, 5.4);
+        let mut sim = cut_cell_sim(24, 24);
+        sim.draw_obstacle(12, 11, radius as f32);
+        let chord = |offset: f64| (radius * radius - offset * offset).max(0.0).sqrt();
+        for y in 0..24 {
+            for x in 0..=24 {
+                let expected = open_length(y as f64, cy, chord(x as f64 - cx));
+                let aperture = sim.u_aperture[(x, y)];
+                assert!(
+                    (aperture - expected).abs() <= 1.0 / SAMPLES as f64,
+                    "u face ({x}, {y}): {aperture} vs {expected}"
+                );
+            }
+        }
+        for y in 0..=24 {
+            for x in 0..24 {
+                let expected = open_length(x as f64, cx, chord(y as f64 - cy));
+                let aperture = sim.v_aperture[(x, y)];
+                assert!(
+                    (aperture - expected).abs() <= 1.0 / SAMPLES as f64,
+                    "v face ({x}, {y}): {aperture} vs {expected}"
+                );
+            }
+        }
+        assert!(sim.is_solid(12, 11) && !sim.is_solid(12, 5));
+    }
+
+    #[test]
+    fn erasing_a_shape_restores_the_fluid() {
+        for cut_cells in [false, true] {
+            let config = SimConfig::builder()
+                .cut_cells(cut_cells)
+                .draw_obstacle(false)
+                .build()
+                .unwrap();
+            let fresh = Simulation::new(20, 16, config.clone());
+            let mut sim = Simulation::new(20, 16, config);
+            let shape = Shape::rotated_rectangle((9.7, 8.2), (7.5, 3.3), 0.6);
+            sim.draw_shape(&shape);
+            assert!(sim.s.data.iter().any(|&s| s != 1.0));
+            sim.erase_shape(&shape);
+            assert_eq!(sim.s.data, fresh.s.data, "cut_cells: {cut_cells}");
+            assert_eq!(sim.u_aperture.data, fresh.u_aperture.data);
+            assert_eq!(sim.v_aperture.data, fresh.v_aperture.data);
+        }
+    }
+
+    #[test]
+    fn clearing_a_cell_keeps_faces_to_solid_cells_shut() {
+        let mut sim = cut_cell_sim(20, 20);
+        sim.draw_shape(&Shape::circle((10.0, 10.0), 6.0));
+        assert!(sim.is_solid(9, 10) && sim.is_solid(10, 10));
+        sim.set_solid(10, 10, false);
+        assert_eq!(sim.u_aperture[(10, 10)], 0.0);
+        assert_eq!(sim.u_aperture[(11, 10)], 0.0);
+        sim.set_solid(9, 10, false);
+        assert_eq!(sim.u_aperture[(10, 10)], 1.0);
+        assert_eq!(sim.u_aperture[(9, 10)], 0.0);
+    }
+}
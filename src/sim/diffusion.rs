@@ -0,0 +1,125 @@
+use super::Simulation;
+use super::interpolation::Grid;
+use crate::config::{Boundary, Diffusion};
+use crate::util::{Array2D, for_each_row};
+
+/// Largest `nu * dt / h^2` for which the explicit update is stable, allowing
+/// for the extra ghost weight next to no-slip walls.
+const EXPLICIT_LIMIT: f64 = 0.2;
+
+impl Simulation {
+    /// Applies viscous diffusion to `u` and `v`.
+    pub(super) fn viscosity(&mut self, dt: f64) {
+        self.sync_periodic_faces();
+        let alpha = self.config.viscosity * dt / (self.config.h * self.config.h);
+        self.u = self.diffuse(&self.u, Grid::U, alpha);
+        self.v = self.diffuse(&self.v, Grid::V, alpha);
+    }
+
+    /// Diffuses `field` by `alpha = k * dt / h^2`. Only entries that advection
+    /// updates are unknowns; the rest, such as faces inside solids or fixed
+    /// inflow faces, act as fixed boundary values. Neighbours beyond the edge of
+    /// the array contribute no flux, except that velocity tangential to a
+    /// no-slip wall is reflected to zero there.
+    pub(super) fn diffuse(&self, field: &Array2D<f64>, grid: Grid, alpha: f64) -> Array2D<f64> {
+        if alpha <= 0.0 {
+            return field.clone();
+        }
+        let explicit = match self.config.diffusion {
+            Diffusion::Explicit => true,
+            Diffusion::Implicit => false,
+            Diffusion::Auto => alpha <= EXPLICIT_LIMIT,
+        };
+
+        if explicit {
+            let mut out = field.clone();
+            for_each_row(&mut out.data, field.width, |j, row| {
+                for (i, q) in row.iter_mut().enumerate() {
+                    if self.advects(grid, i, j) {
+                        let (sum, weight) = self.neighbour_sum(field, grid, i, j);
+                        *q += alpha * (sum - weight * field[(i, j)]);
+                    }
+                }
+            });
+            return out;
+        }
+
+        // Jacobi iterations on (1 + alpha * count) q - alpha * sum(neighbours) = field.
+        let mut current = field.clone();
+        for _ in 0..self.config.diffusion_iterations {
+            let mut next = current.clone();
+            for_each_row(&mut next.data, field.width, |j, row| {
+                for (i, q) in row.iter_mut().enumerate() {
+                    if self.advects(grid, i, j) {
+                        let (sum, weight) = self.neighbour_sum(&current, grid, i, j);
+                        *q = (field[(i, j)] + alpha * sum) / (1.0 + alpha * weight);
+                    }
+                }
+            });
+            current = next;
+        }
+        current
+    }
+
+    /// Sum of the in-bounds 4-neighbours of `(i, j)` and the weight of the
+    /// centre sample in the discrete Laplacian. A no-slip ghost sample mirrors
+    /// the centre with opposite sign, so it adds two to the weight instead of
+    /// appearing in the sum. Across a periodic edge the neighbour wraps around.
+    fn neighbour_sum(&self, field: &Array2D<f64>, grid: Grid, i: usize, j: usize) -> (f64, f64) {
+        let b = self.config.boundaries;
+        // Walls tangential to this grid, as [left, right, top, bottom].
+        let walls = match grid {
+            Grid::U => [None, None, Some(b.top), Some(b.bottom)],
+            Grid::V => [Some(b.left), Some(b.right), None, None],
+            Grid::Cell => [None; 4],
+        };
+        let (periodic_x, periodic_y) = self.periodic();
+        let ghost = |side: usize| {
+            if walls[side] == Some(Boundary::NoSlip) {
+                2.0
+            } else {
+                0.0
+            }
+        };
+
+        let mut sum = 0.0;
+        let mut weight = 0.0;
+        if i > 0 {
+            sum += field[(i - 1, j)];
+            weight += 1.0;
+        } else if periodic_x {
+            sum += field[(self.width - 1, j)];
+            weight += 1.0;
+        } else {
+            weight += ghost(0);
+        }
+        if i + 1 < field.width {
+            sum += field[(i + 1, j)];
+            weight += 1.0;
+        } else if periodic_x {
+            sum += field[(0, j)];
+            weight += 1.0;
+        } else {
+            weight += ghost(1);
+        }
+        if j > 0 {
+            sum += field[(i, j - 1)];
+            weight += 1.0;
+        } else if periodic_y {
+            sum += field[(i, self.height - 1)];
+            weight += 1.0;
+        } else {
+            weight += ghost(2);
+        }
+        if j + 1 < field.height {
+            sum += field[(i, j + 1)];
+            weight += 1.0;
+        } else if periodic_y {
+            sum += field[(i, 0)];
+            weight += 1.0;
+        } else {
+            weight += ghost(3);
+        }
+        (sum, weight)
+    }
+}
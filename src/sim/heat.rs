@@ -0,0 +1,115 @@
+use super::Simulation;
+use super::interpolation::Grid;
+
+/// A disc of cells held at a fixed temperature. Sources hotter than
+/// `ambient_temperature` heat the fluid around them, colder ones act as sinks.
+/// A source placed over an obstacle makes it a heated body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeatSource {
+    pub x: i32,
+    pub y: i32,
+    pub radius: f32,
+    pub temperature: f64,
+}
+
+impl Simulation {
+    pub(super) fn apply_heat_sources(&mut self) {
+        let period = self.periods();
+        for source in &self.heat_sources {
+            self.temperature.fill_circle_wrapping(
+                source.x,
+                source.y,
+                source.radius,
+                source.temperature,
+                period,
+            );
+        }
+    }
+
+    /// Boussinesq buoyancy on every open `v` face: smoke weighs the fluid
+    /// down and temperature above ambient lifts it. Positive `v` points down,
+    /// matching `gravitation`. On a y-periodic domain the first row of faces
+    /// averages the last row of cells with the first.
+    pub(super) fn buoyancy(&mut self, dt: f64) {
+        let alpha = self.config.smoke_weight;
+        let beta = self.config.thermal_expansion;
+        let ambient = self.config.ambient_temperature;
+        let periodic_y = self.periodic().1;
+        for y in 0..self.height {
+            let Some(above) = y.checked_sub(1).or(periodic_y.then(|| self.height - 1)) else {
+                continue;
+            };
+            for x in 0..self.width {
+                if self.open_v(x, y) {
+                    let smoke = 0.5 * (self.smoke[(x, above)] + self.smoke[(x, y)]);
+                    let temperature =
+                        0.5 * (self.temperature[(x, above)] + self.temperature[(x, y)]);
+                    self.v[(x, y)] += dt * (alpha * smoke - beta * (temperature - ambient));
+                }
+            }
+        }
+    }
+
+    pub(super) fn temperature_advection(&mut self, dt: f64) {
+        self.temperature = self.advect(&self.temperature, Grid::Cell, dt);
+        if self.config.thermal_diffusivity > 0.0 {
+            let alpha = self.config.thermal_diffusivity * dt / (self.config.h * self.config.h);
+            self.temperature = self.diffuse(&self.temperature, Grid::Cell, alpha);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeatSource;
+    use crate::config::{Boundaries, Boundary, SimConfig};
+    use crate::sim::Simulation;
+
+    #[test]
+    fn sources_wrap_around_periodic_edges() {
+        let config = SimConfig::builder()
+            .boundaries(Boundaries {
+                top: Boundary::Periodic,
+                bottom: Boundary::Periodic,
+                ..Boundaries::default()
+            })
+            .draw_obstacle(false)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(10, 20, config);
+        sim.add_heat_source(HeatSource {
+            x: 5,
+            y: 19,
+            radius: 2.0,
+            temperature: 3.0,
+        });
+        sim.apply_heat_sources();
+        assert_eq!(sim.temperature[(5, 19)], 3.0);
+        assert_eq!(sim.temperature[(5, 1)], 3.0);
+        assert_eq!(sim.temperature[(4, 0)], 3.0);
+        assert_eq!(sim.temperature[(5, 2)], 0.0);
+    }
+
+    #[test]
+    fn buoyancy_lifts_the_first_faces_of_a_periodic_domain() {
+        let config = SimConfig::builder()
+            .boundaries(Boundaries {
+                top: Boundary::Periodic,
+                bottom: Boundary::Periodic,
+                ..Boundaries::default()
+            })
+            .draw_obstacle(false)
+            .thermal_expansion(2.0)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(6, 8, config);
+        for x in 0..6 {
+            sim.temperature[(x, 7)] = 1.0;
+        }
+        sim.buoyancy(0.5);
+        assert_eq!(sim.v[(3, 0)], -0.5);
+        assert_eq!(sim.v[(3, 7)], -0.5);
+        assert_eq!(sim.v[(3, 4)], 0.0);
+    }
+}
@@ -0,0 +1,342 @@
+use super::Simulation;
+use crate::config::{Boundary, Interpolation};
+use crate::util::Array2D;
+use std::cmp::min;
+
+/// Which staggered grid a quantity lives on; determines where its samples sit
+/// inside a cell and which entries advection updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Grid {
+    U,
+    V,
+    Cell,
+}
+
+impl Grid {
+    /// Sample position inside a cell, in units of `h`.
+    pub(super) fn offset(self) -> (f64, f64) {
+        match self {
+            Grid::U => (0.0, 0.5),
+            Grid::V => (0.5, 0.0),
+            Grid::Cell => (0.5, 0.5),
+        }
+    }
+}
+
+/// The four samples surrounding a point, with their bilinear weights. Next
+/// to a no-slip wall the rest of the weight goes to the zero velocity at the
+/// wall.
+pub(super) struct Stencil {
+    points: [(usize, usize); 4],
+    weights: [f64; 4],
+    wall: f64,
+}
+
+impl Stencil {
+    fn interpolate(&self, field: &Array2D<f64>) -> f64 {
+        self.weights[0] * field[self.points[0]]
+            + self.weights[1] * field[self.points[1]]
+            + self.weights[2] * field[self.points[2]]
+            + self.weights[3] * field[self.points[3]]
+    }
+
+    pub(super) fn bounds(&self, field: &Array2D<f64>) -> (f64, f64) {
+        let (lo, hi) = self
+            .points
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(lo, hi), &p| {
+                (lo.min(field[p]), hi.max(field[p]))
+            });
+        if self.wall > 0.0 {
+            (lo.min(0.0), hi.max(0.0))
+        } else {
+            (lo, hi)
+        }
+    }
+}
+
+/// Lower sample index and fractional offset along each axis.
+struct Location {
+    x0: usize,
+    tx: f64,
+    y0: usize,
+    ty: f64,
+}
+
+impl Simulation {
+    /// Samples `field` at world position `(x, y)` with the configured
+    /// interpolation. Points outside the domain are clamped to its edge, or
+    /// wrapped around along a periodic axis. Velocity along a no-slip wall
+    /// falls to zero at the wall, see `no_slip_scale`.
+    pub(super) fn sample(&self, field: &Array2D<f64>, grid: Grid, x: f64, y: f64) -> f64 {
+        let mut at = self.locate(grid, x, y);
+        let scale = self.no_slip_scale(grid, x, y, &mut at);
+        match self.config.interpolation {
+            Interpolation::Bilinear => self.bilinear(&at, scale).interpolate(field),
+            Interpolation::CatmullRom => {
+                let (lo, hi) = self.bilinear(&at, scale).bounds(field);
+                (scale * self.bicubic(field, &at, catmull_rom)).clamp(lo, hi)
+            }
+            Interpolation::MonotoneCubic => scale * self.bicubic(field, &at, monotone_cubic),
+        }
+    }
+
+    pub(super) fn sample_u(&self, x: f64, y: f64) -> f64 {
+        self.sample(&self.u, Grid::U, x, y)
+    }
+
+    pub(super) fn sample_v(&self, x: f64, y: f64) -> f64 {
+        self.sample(&self.v, Grid::V, x, y)
+    }
+
+    pub(super) fn sample_velocity(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.sample_u(x, y), self.sample_v(x, y))
+    }
+
+    /// The bilinear stencil around `(x, y)`, whatever the configured
+    /// interpolation; used to bound higher-order reconstructions.
+    pub(super) fn stencil(&self, grid: Grid, x: f64, y: f64) -> Stencil {
+        let mut at = self.locate(grid, x, y);
+        let scale = self.no_slip_scale(grid, x, y, &mut at);
+        self.bilinear(&at, scale)
+    }
+
+    fn locate(&self, grid: Grid, x: f64, y: f64) -> Location {
+        let h = self.config.h;
+        let (ox, oy) = grid.offset();
+        let (periodic_x, periodic_y) = self.periodic();
+        let (x0, tx) = locate_axis(x, ox * h, h, self.width, periodic_x);
+        let (y0, ty) = locate_axis(y, oy * h, h, self.height, periodic_y);
+        Location { x0, tx, y0, ty }
+    }
+
+    /// Within half a cell of a no-slip wall, the velocity along the wall is
+    /// interpolated between the first row of samples and a ghost sample
+    /// mirrored beyond the wall with the opposite sign, so it falls linearly
+    /// to zero at the wall. Moves `at` onto that row and returns the factor
+    /// its value is scaled by; 1 for every other sample.
+    fn no_slip_scale(&self, grid: Grid, x: f64, y: f64, at: &mut Location) -> f64 {
+        let h = self.config.h;
+        let walls = &self.config.boundaries;
+        let (pos, cells, first, last, index, t) = match grid {
+            Grid::U => (
+                y,
+                self.height,
+                walls.top,
+                walls.bottom,
+                &mut at.y0,
+                &mut at.ty,
+            ),
+            Grid::V => (
+                x,
+                self.width,
+                walls.left,
+                walls.right,
+                &mut at.x0,
+                &mut at.tx,
+            ),
+            Grid::Cell => return 1.0,
+        };
+        let len = cells as f64 * h;
+        if first == Boundary::NoSlip && pos < 0.5 * h {
+            (*index, *t) = (0, 0.0);
+            pos.max(0.0) / (0.5 * h)
+        } else if last == Boundary::NoSlip && pos > len - 0.5 * h {
+            (*index, *t) = (cells - 1, 0.0);
+            (len - pos).max(0.0) / (0.5 * h)
+        } else {
+            1.0
+        }
+    }
+
+    /// Bilinear weights around `at`, all scaled by `scale`.
+    fn bilinear(&self, at: &Location, scale: f64) -> Stencil {
+        let (periodic_x, periodic_y) = self.periodic();
+        let x1 = if periodic_x {
+            (at.x0 + 1) % self.width
+        } else {
+            min(at.x0 + 1, self.width - 1)
+        };
+        let y1 = if periodic_y {
+            (at.y0 + 1) % self.height
+        } else {
+            min(at.y0 + 1, self.height - 1)
+        };
+
+        let sx = 1.0 - at.tx;
+        let sy = 1.0 - at.ty;
+
+        Stencil {
+            points: [(at.x0, at.y0), (x1, at.y0), (x1, y1), (at.x0, y1)],
+            weights: [sx * sy, at.tx * sy, at.tx * at.ty, sx * at.ty].map(|w| w * scale),
+            wall: 1.0 - scale,
+        }
+    }
+
+    /// Tensor-product cubic over the 4x4 samples around `at`, interpolating
+    /// each row in x and then the row results in y.
+    fn bicubic(&self, field: &Array2D<f64>, at: &Location, cubic: fn([f64; 4], f64) -> f64) -> f64 {
+        let (periodic_x, periodic_y) = self.periodic();
+        let index = |base: usize, k: usize, len: usize, periodic: bool| {
+            if periodic {
+                (base + k + len - 1) % len
+            } else {
+                (base + k).saturating_sub(1).min(len - 1)
+            }
+        };
+        let mut rows = [0.0; 4];
+        for (ky, row) in rows.iter_mut().enumerate() {
+            let y = index(at.y0, ky, self.height, periodic_y);
+            let values =
+                [0, 1, 2, 3].map(|kx| field[(index(at.x0, kx, self.width, periodic_x), y)]);
+            *row = cubic(values, at.tx);
+        }
+        cubic(rows, at.ty)
+    }
+}
+
+/// Lower sample index and fraction along one axis of `cells` cells, for
+/// samples offset by `offset` from the cell corners. Positions are clamped to
+/// `[h, cells * h]`, or wrapped into the domain when `periodic`.
+fn locate_axis(pos: f64, offset: f64, h: f64, cells: usize, periodic: bool) -> (usize, f64) {
+    if periodic {
+        let pos = pos.rem_euclid(cells as f64 * h) - offset;
+        let cell = (pos / h).floor();
+        let t = (pos - cell * h) / h;
+        return ((cell as i64).rem_euclid(cells as i64) as usize, t);
+    }
+    let pos = h.max(pos.min(cells as f64 * h));
+    let i0 = min(((pos - offset) / h).floor() as usize, cells - 1);
+    let t = ((pos - offset) - i0 as f64 * h) / h;
+    (i0, t)
+}
+
+/// Catmull-Rom spline through `p[1]` and `p[2]`.
+fn catmull_rom(p: [f64; 4], t: f64) -> f64 {
+    p[1] + 0.5
+        * t
+        * (p[2] - p[0]
+            + t * (2.0 * p[0] - 5.0 * p[1] + 4.0 * p[2] - p[3]
+                + t * (3.0 * (p[1] - p[2]) + p[3] - p[0])))
+}
+
+/// Cubic Hermite between `p[1]` and `p[2]` with Fritsch-Carlson limited
+/// slopes, so the result never leaves `[p[1], p[2]]`.
+fn monotone_cubic(p: [f64; 4], t: f64) -> f64 {
+    let delta = p[2] - p[1];
+    let mut m1 = 0.5 * (p[2] - p[0]);
+    let mut m2 = 0.5 * (p[3] - p[1]);
+    if delta == 0.0 {
+        m1 = 0.0;
+        m2 = 0.0;
+    } else {
+        if m1 * delta <= 0.0 {
+            m1 = 0.0;
+        }
+        if m2 * delta <= 0.0 {
+            m2 = 0.0;
+        }
+        let alpha = m1 / delta;
+        let beta = m2 / delta;
+        let r = alpha * alpha + beta * beta;
+        if r > 9.0 {
+            let tau = 3.0 / r.sqrt();
+            m1 = tau * alpha * delta;
+            m2 = tau * beta * delta;
+        }
+    }
+
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p[1]
+        + (t3 - 2.0 * t2 + t) * m1
+        + (-2.0 * t3 + 3.0 * t2) * p[2]
+        + (t3 - t2) * m2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, monotone_cubic};
+    use crate::config::{Interpolation, SimConfig};
+    use crate::sim::Simulation;
+    use crate::util::Array2D;
+
+    /// Deterministic values in `[-1, 1)` from a linear congruential generator.
+    fn noise(count: usize) -> Vec<f64> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..count)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stays_between_the_middle_samples() {
+        let mut cases = vec![
+            [0.0, 0.0, 1.0, 1.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [1.0, 0.0, 1.0, 0.0],
+            [0.0, 0.99, 1.0, 100.0],
+            [-100.0, 0.0, 0.01, 0.01],
+        ];
+        cases.extend(noise(400).chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]));
+        for p in cases {
+            let (lo, hi) = (p[1].min(p[2]), p[1].max(p[2]));
+            let mut previous = p[1];
+            for k in 0..=40 {
+                let value = monotone_cubic(p, k as f64 / 40.0);
+                assert!(
+                    lo - 1e-12 <= value && value <= hi + 1e-12,
+                    "{:?}: {}",
+                    p,
+                    value
+                );
+                // Monotone between the samples, in the direction they go.
+                assert!((value - previous) * (p[2] - p[1]) >= -1e-12, "{:?}", p);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn interpolates_samples_and_straight_lines() {
+        let p = [3.0, -1.0, 2.0, 7.0];
+        assert_eq!(monotone_cubic(p, 0.0), -1.0);
+        assert_eq!(monotone_cubic(p, 1.0), 2.0);
+        for k in 0..=10 {
+            let t = k as f64 / 10.0;
+            let value = monotone_cubic([1.0, 3.0, 5.0, 7.0], t);
+            assert!((value - (3.0 + 2.0 * t)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn grid_samples_stay_within_their_stencil() {
+        let config = SimConfig::builder()
+            .interpolation(Interpolation::MonotoneCubic)
+            .build()
+            .unwrap();
+        let sim = Simulation::new(16, 12, config);
+        let mut field = Array2D::new(16, 12);
+        for (value, noise) in field.data.iter_mut().zip(noise(16 * 12)) {
+            // Steps and spikes are where an unlimited cubic overshoots most.
+            *value = if noise > 0.5 {
+                10.0 * noise
+            } else {
+                noise.round()
+            };
+        }
+        let h = sim.config.h;
+        for (i, offset) in noise(2 * 500).chunks_exact(2).enumerate() {
+            let x = (i % 25) as f64 * 0.64 * h + offset[0].abs() * h;
+            let y = (i / 25) as f64 * 0.6 * h + offset[1].abs() * h;
+            let value = sim.sample(&field, Grid::Cell, x, y);
+            let (lo, hi) = sim.stencil(Grid::Cell, x, y).bounds(&field);
+            assert!(lo - 1e-12 <= value && value <= hi + 1e-12, "({}, {})", x, y);
+        }
+    }
+}
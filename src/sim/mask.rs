@@ -0,0 +1,44 @@
+use super::Simulation;
+use crate::image::{Image, ImageChannel, MaskMode};
+
+impl Simulation {
+    /// Replaces every obstacle with the solid cells of `image`, which is
+    /// stretched over the whole grid.
+    pub fn load_mask(&mut self, image: &Image, mode: MaskMode) {
+        self.clear_obstacles();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if mode.is_solid(image, x, y, self.width, self.height) {
+                    self.set_solid(x, y, true);
+                }
+            }
+        }
+    }
+
+    /// Holds the smoke of every cell at `channel` of `image`, stretched over
+    /// the grid, replacing any previous source map. Cells where the channel is
+    /// zero are left alone.
+    pub fn set_smoke_sources(&mut self, image: &Image, channel: ImageChannel) {
+        self.smoke_sources.clear();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let value = image.sample(channel, x, y, self.width, self.height);
+                if value > 0.0 {
+                    self.smoke_sources.push((x, y, value));
+                }
+            }
+        }
+    }
+
+    pub fn clear_smoke_sources(&mut self) {
+        self.smoke_sources.clear();
+    }
+
+    pub(super) fn apply_smoke_sources(&mut self) {
+        for &(x, y, value) in &self.smoke_sources {
+            if !self.is_solid(x, y) {
+                self.smoke[(x, y)] = value;
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use super::{DivergenceStats, FLUID, Simulation, SolveStats};
+use crate::config::MultigridCycle;
+use crate::util::Array2D;
+
+const PRE_SMOOTH: usize = 2;
+const POST_SMOOTH: usize = 2;
+const COARSEST_SWEEPS: usize = 40;
+const COARSEST_SIZE: usize = 4;
+
+/// One level of the grid hierarchy. `wx` and `wy` hold the open weight of each
+/// cell face, so the solid mask is carried down to every coarse level. On a
+/// periodic axis the first and last faces are the same face.
+struct Level {
+    width: usize,
+    height: usize,
+    periodic: (bool, bool),
+    /// Cell boundaries along each axis, in finest-level cells. Coarse cells
+    /// are two fine cells wide except the last one on an odd-sized level.
+    edges_x: Vec<usize>,
+    edges_y: Vec<usize>,
+    fluid: Array2D<bool>,
+    wx: Array2D<f64>,
+    wy: Array2D<f64>,
+    x: Array2D<f64>,
+    b: Array2D<f64>,
+    r: Array2D<f64>,
+}
+
+impl Level {
+    fn new(width: usize, height: usize, periodic: (bool, bool)) -> Self {
+        Level {
+            width,
+            height,
+            periodic,
+            edges_x: (0..=width).collect(),
+            edges_y: (0..=height).collect(),
+            fluid: Array2D::new(width, height),
+            wx: Array2D::new(width + 1, height),
+            wy: Array2D::new(width, height + 1),
+            x: Array2D::new(width, height),
+            b: Array2D::new(width, height),
+            r: Array2D::new(width, height),
+        }
+    }
+
+    fn diag(&self, x: usize, y: usize) -> f64 {
+        self.wx[(x, y)] + self.wx[(x + 1, y)] + self.wy[(x, y)] + self.wy[(x, y + 1)]
+    }
+
+    /// Weighted sum of the neighbouring unknowns, i.e. the off-diagonal part of
+    /// `A x` with its sign flipped.
+    fn neighbours(&self, x: usize, y: usize) -> f64 {
+        let mut sum = 0.0;
+        let (periodic_x, periodic_y) = self.periodic;
+        if x > 0 {
+            sum += self.wx[(x, y)] * self.x[(x - 1, y)];
+        } else if periodic_x {
+            sum += self.wx[(x, y)] * self.x[(self.width - 1, y)];
+        }
+        if x + 1 < self.width {
+            sum += self.wx[(x + 1, y)] * self.x[(x + 1, y)];
+        } else if periodic_x {
+            sum += self.wx[(x + 1, y)] * self.x[(0, y)];
+        }
+        if y > 0 {
+            sum += self.wy[(x, y)] * self.x[(x, y - 1)];
+        } else if periodic_y {
+            sum += self.wy[(x, y)] * self.x[(x, self.height - 1)];
+        }
+        if y + 1 < self.height {
+            sum += self.wy[(x, y + 1)] * self.x[(x, y + 1)];
+        } else if periodic_y {
+            sum += self.wy[(x, y + 1)] * self.x[(x, 0)];
+        }
+        sum
+    }
+
+    /// Red-black Gauss-Seidel sweeps.
+    fn smooth(&mut self, sweeps: usize) {
+        for _ in 0..sweeps {
+            for color in 0..2 {
+                for y in 0..self.height {
+                    for x in ((y + color) % 2..self.width).step_by(2) {
+                        if !self.fluid[(x, y)] {
+                            continue;
+                        }
+                        let diag = self.diag(x, y);
+                        if diag > 0.0 {
+                            self.x[(x, y)] = (self.b[(x, y)] + self.neighbours(x, y)) / diag;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn compute_residual(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let r = if self.fluid[(x, y)] {
+                    self.b[(x, y)] - self.diag(x, y) * self.x[(x, y)] + self.neighbours(x, y)
+                } else {
+                    0.0
+                };
+                self.r[(x, y)] = r;
+            }
+        }
+    }
+
+    /// Builds the next coarser level: a coarse cell is fluid if any of its
+    /// children is. Each weight is the open length of a face divided by the
+    /// distance between the pressures on either side of it, so a coarse face
+    /// takes the fine faces it covers, each scaled back to its length, over
+    /// the distance between the coarse cell centres. Domain-edge faces measure
+    /// that distance to where the finest level fixes an open edge's pressure,
+    /// half a fine cell outside the domain.
+    fn coarsen(&self) -> Level {
+        let mut coarse = Level::new(
+            self.width.div_ceil(2),
+            self.height.div_ceil(2),
+            self.periodic,
+        );
+        coarse.edges_x = (0..=coarse.width)
+            .map(|i| self.edges_x[(2 * i).min(self.width)])
+            .collect();
+        coarse.edges_y = (0..=coarse.height)
+            .map(|i| self.edges_y[(2 * i).min(self.height)])
+            .collect();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.fluid[(x, y)] {
+                    coarse.fluid[(x / 2, y / 2)] = true;
+                }
+            }
+        }
+
+        let fine_x = face_distances(&self.edges_x, self.periodic.0);
+        let fine_y = face_distances(&self.edges_y, self.periodic.1);
+        let coarse_x = face_distances(&coarse.edges_x, self.periodic.0);
+        let coarse_y = face_distances(&coarse.edges_y, self.periodic.1);
+        for cy in 0..coarse.height {
+            for (cx, &distance) in coarse_x.iter().enumerate() {
+                let fx = (2 * cx).min(self.width);
+                let rows = (2 * cy..(2 * cy + 2).min(self.height)).map(|fy| self.wx[(fx, fy)]);
+                coarse.wx[(cx, cy)] = rows.sum::<f64>() * fine_x[fx] / distance;
+            }
+        }
+        for (cy, &distance) in coarse_y.iter().enumerate() {
+            for cx in 0..coarse.width {
+                let fy = (2 * cy).min(self.height);
+                let cols = (2 * cx..(2 * cx + 2).min(self.width)).map(|fx| self.wy[(fx, fy)]);
+                coarse.wy[(cx, cy)] = cols.sum::<f64>() * fine_y[fy] / distance;
+            }
+        }
+        coarse
+    }
+}
+
+/// Distance, in finest-level cells, between the pressures on either side of
+/// each face along an axis with cell boundaries `edges`. On a periodic axis
+/// the first and last faces span the wrap-around.
+fn face_distances(edges: &[usize], periodic: bool) -> Vec<f64> {
+    let cells = edges.len() - 1;
+    let length = edges[cells] as f64;
+    let centre = |k: usize| (edges[k] + edges[k + 1]) as f64 / 2.0;
+    let wrap = centre(0) + length - centre(cells - 1);
+    (0..=cells)
+        .map(|i| {
+            if i == 0 {
+                if periodic { wrap } else { centre(0) + 0.5 }
+            } else if i == cells {
+                if periodic {
+                    wrap
+                } else {
+                    length + 0.5 - centre(cells - 1)
+                }
+            } else {
+                centre(i) - centre(i - 1)
+            }
+        })
+        .collect()
+}
+
+/// Restricts `fine.r` into `coarse.b` by summing children and clears the
+/// coarse initial guess.
+fn restrict(fine: &Level, coarse: &mut Level) {
+    coarse.b.zero();
+    coarse.x.zero();
+    for y in 0..fine.height {
+        for x in 0..fine.width {
+            coarse.b[(x / 2, y / 2)] += fine.r[(x, y)];
+        }
+    }
+}
+
+/// Adds the coarse correction back onto the fluid cells of the fine level.
+fn prolongate(coarse: &Level, fine: &mut Level) {
+    for y in 0..fine.height {
+        for x in 0..fine.width {
+            if fine.fluid[(x, y)] {
+                fine.x[(x, y)] += coarse.x[(x / 2, y / 2)];
+            }
+        }
+    }
+}
+
+fn cycle(levels: &mut [Level], kind: MultigridCycle) {
+    let (fine, coarser) = levels.split_first_mut().unwrap();
+    if coarser.is_empty() {
+        fine.smooth(COARSEST_SWEEPS);
+        return;
+    }
+    fine.smooth(PRE_SMOOTH);
+    fine.compute_residual();
+    restrict(fine, &mut coarser[0]);
+    match kind {
+        MultigridCycle::V => cycle(coarser, MultigridCycle::V),
+        MultigridCycle::F => {
+            cycle(coarser, MultigridCycle::F);
+            cycle(coarser, MultigridCycle::V);
+        }
+    }
+    prolongate(&coarser[0], fine);
+    fine.smooth(POST_SMOOTH);
+}
+
+impl Simulation {
+    /// Solves the same system as `pcg_projection` with geometric multigrid
+    /// cycles until the divergence drops below `solver_tolerance`.
+    pub(super) fn multigrid_projection(&mut self, dt: f64, kind: MultigridCycle) -> SolveStats {
+        let scale = self.config.density * self.config.h / dt;
+        let mut levels = vec![self.finest_level()];
+        loop {
+            let coarsest = levels.last().unwrap();
+            if coarsest.width.min(coarsest.height) <= COARSEST_SIZE {
+                break;
+            }
+            levels.push(coarsest.coarsen());
+        }
+
+        let norm = self.config.divergence_norm;
+        let fluid_cells = self.fluid_cells();
+        let residual = |level: &mut Level| {
+            level.compute_residual();
+            DivergenceStats::from_residual(&level.r.data, fluid_cells).norm(norm)
+        };
+
+        let mut stats = SolveStats {
+            iterations: 0,
+            residual: residual(&mut levels[0]),
+        };
+        while stats.residual > self.config.solver_tolerance
+            && stats.iterations < self.config.max_solver_iterations
+        {
+            cycle(&mut levels, kind);
+            stats.iterations += 1;
+            stats.residual = residual(&mut levels[0]);
+        }
+
+        let q = &levels[0].x;
+        self.apply_pressure_gradient(q);
+        for i in 0..self.p.data.len() {
+            self.p.data[i] = q.data[i] * scale;
+        }
+        stats
+    }
+
+    fn finest_level(&self) -> Level {
+        let mut level = Level::new(self.width, self.height, self.periodic());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.s[(x, y)] != FLUID {
+                    continue;
+                }
+                level.fluid[(x, y)] = true;
+                level.b[(x, y)] = -self.divergence(x, y);
+                level.wx[(x, y)] = self.u_weight(x, y);
+                level.wx[(x + 1, y)] = self.u_weight(x + 1, y);
+                level.wy[(x, y)] = self.v_weight(x, y);
+                level.wy[(x, y + 1)] = self.v_weight(x, y + 1);
+            }
+        }
+        level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Boundaries, Boundary, MultigridCycle, PressureSolver, SimConfig};
+    use crate::sim::Simulation;
+    use crate::sim::testing::assert_converges;
+
+    /// Checks both cycles with `assert_converges`, starting with or without
+    /// the default obstacle.
+    fn assert_cycles_converge(width: usize, height: usize, boundaries: Boundaries, obstacle: bool) {
+        for cycle in [MultigridCycle::V, MultigridCycle::F] {
+            let config = SimConfig::builder()
+                .pressure_solver(PressureSolver::Multigrid(cycle))
+                .boundaries(boundaries)
+                .draw_obstacle(obstacle)
+                .max_solver_iterations(50)
+                .build()
+                .unwrap();
+            assert_converges(config, width, height);
+        }
+    }
+
+    #[test]
+    fn converges_with_pressure_outlet_on_odd_sizes() {
+        let outlet = Boundaries {
+            right: Boundary::PressureOutlet,
+            ..Boundaries::default()
+        };
+        assert_cycles_converge(17, 17, outlet, false);
+        assert_cycles_converge(100, 100, outlet, true);
+        assert_cycles_converge(
+            37,
+            23,
+            Boundaries {
+                top: Boundary::PressureOutlet,
+                bottom: Boundary::PressureOutlet,
+                ..Boundaries::default()
+            },
+            true,
+        );
+    }
+
+    #[test]
+    fn converges_with_walls_on_odd_sizes() {
+        assert_cycles_converge(50, 50, Boundaries::default(), true);
+        assert_cycles_converge(27, 45, Boundaries::default(), true);
+    }
+
+    #[test]
+    fn converges_on_periodic_axes() {
+        let torus = Boundaries {
+            left: Boundary::Periodic,
+            right: Boundary::Periodic,
+            top: Boundary::Periodic,
+            bottom: Boundary::Periodic,
+        };
+        assert_cycles_converge(64, 32, torus, true);
+        assert_cycles_converge(33, 21, torus, true);
+    }
+
+    /// Both solvers discretise the same system, so once converged they must
+    /// leave the same velocities behind.
+    #[test]
+    fn matches_conjugate_gradient() {
+        let velocities = |solver| {
+            let config = SimConfig::builder()
+                .pressure_solver(solver)
+                .solver_tolerance(1e-9)
+                .max_solver_iterations(500)
+                .build()
+                .unwrap();
+            let mut sim = Simulation::new(45, 31, config);
+            sim.step();
+            (sim.get_u().to_vec(), sim.get_v().to_vec())
+        };
+        let (u, v) = velocities(PressureSolver::ConjugateGradient);
+        for cycle in [MultigridCycle::V, MultigridCycle::F] {
+            let (mg_u, mg_v) = velocities(PressureSolver::Multigrid(cycle));
+            let difference = u
+                .iter()
+                .zip(&mg_u)
+                .chain(v.iter().zip(&mg_v))
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            assert!(difference < 1e-6, "{:?}: {}", cycle, difference);
+        }
+    }
+}
@@ -0,0 +1,572 @@
+use super::{DivergenceStats, FLUID, Simulation};
+use crate::config::SorOrdering;
+use crate::util::{Array2D, for_each_row};
+
+/// Tuning constants for the MIC(0) preconditioner, as in Bridson's
+/// "Fluid Simulation for Computer Graphics".
+const MIC_TAU: f64 = 0.97;
+const MIC_SIGMA: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SolveStats {
+    pub iterations: u32,
+    /// Divergence left behind by the solve, measured with the configured
+    /// `divergence_norm`.
+    pub residual: f64,
+}
+
+/// The pressure Poisson matrix restricted to fluid cells. Off-diagonal
+/// entries are stored once per cell for its +x and +y neighbours; on a
+/// periodic axis the last cell's entry couples it to the first.
+struct Poisson {
+    diag: Array2D<f64>,
+    plus_x: Array2D<f64>,
+    plus_y: Array2D<f64>,
+    periodic: (bool, bool),
+}
+
+impl Simulation {
+    pub(super) fn sor_projection(&mut self, dt: f64) -> SolveStats {
+        let tolerance = self.config.solver_tolerance;
+        let norm = self.config.divergence_norm;
+        self.p.zero();
+        let mut stats = SolveStats::default();
+        while stats.iterations < self.config.projection_iterations {
+            stats.iterations += 1;
+            match self.config.sor_ordering {
+                SorOrdering::Lexicographic => self.sor_sweep(dt),
+                SorOrdering::RedBlack => {
+                    self.red_black_sweep(0, dt);
+                    self.red_black_sweep(1, dt);
+                }
+            }
+            if self.config.sor_until_converged && self.divergence_stats().norm(norm) <= tolerance {
+                break;
+            }
+        }
+        stats.residual = self.divergence_stats().norm(norm);
+        stats
+    }
+
+    fn sor_sweep(&mut self, dt: f64) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.relax(x, y, dt);
+            }
+        }
+    }
+
+    /// One overrelaxed Gauss-Seidel update of cell `(x, y)`, if it is fluid.
+    fn relax(&mut self, x: usize, y: usize, dt: f64) {
+        if self.s[(x, y)] != FLUID {
+            return;
+        }
+        let h = self.config.h;
+        let density = self.config.density;
+        let d = self.config.overrelaxation * self.divergence(x, y);
+        let s1 = self.s(x as i32 - 1, y as i32);
+        let s2 = self.s(x as i32 + 1, y as i32);
+        let s3 = self.s(x as i32, y as i32 - 1);
+        let s4 = self.s(x as i32, y as i32 + 1);
+        let (right, below) = (self.wrap_u(x + 1), self.wrap_v(y + 1));
+        // Faces are weighted by their open fraction, so each open face
+        // moves by the same amount and the weighted flux closes.
+        let s = s1 * self.u_aperture[(x, y)]
+            + s2 * self.u_aperture[(right, y)]
+            + s3 * self.v_aperture[(x, y)]
+            + s4 * self.v_aperture[(x, below)];
+        if s == 0.0 {
+            return;
+        }
+        self.u[(x, y)] += d * s1 / s;
+        self.u[(right, y)] -= d * s2 / s;
+        self.v[(x, y)] += d * s3 / s;
+        self.v[(x, below)] -= d * s4 / s;
+
+        self.p[(x, y)] -= d / s * density * h / dt;
+    }
+
+    /// Relaxes every cell with `(x + y) % 2 == color`. Cells of one color share
+    /// no faces, so all their corrections are computed first and then applied
+    /// row by row, giving the same result whether or not rows run in parallel.
+    fn red_black_sweep(&mut self, color: usize, dt: f64) {
+        let h = self.config.h;
+        let density = self.config.density;
+        let overrelaxation = self.config.overrelaxation;
+        let (width, height) = (self.width, self.height);
+        let (periodic_x, periodic_y) = self.periodic();
+
+        // (overrelaxed divergence, sum of face weights); zero weight sum
+        // marks cells that are skipped this sweep.
+        let mut corrections: Array2D<(f64, f64)> = Array2D::new(width, height);
+        for_each_row(&mut corrections.data, width, |y, row| {
+            for x in ((y + color) % 2..width).step_by(2) {
+                if self.s[(x, y)] != FLUID {
+                    continue;
+                }
+                let (xi, yi) = (x as i32, y as i32);
+                let (right, below) = (self.wrap_u(x + 1), self.wrap_v(y + 1));
+                let s = self.s(xi - 1, yi) * self.u_aperture[(x, y)]
+                    + self.s(xi + 1, yi) * self.u_aperture[(right, y)]
+                    + self.s(xi, yi - 1) * self.v_aperture[(x, y)]
+                    + self.s(xi, yi + 1) * self.v_aperture[(x, below)];
+                row[x] = (overrelaxation * self.divergence(x, y), s);
+            }
+        });
+
+        let mut u = std::mem::replace(&mut self.u, Array2D::new(0, 0));
+        let mut v = std::mem::replace(&mut self.v, Array2D::new(0, 0));
+        let mut p = std::mem::replace(&mut self.p, Array2D::new(0, 0));
+        {
+            let sim = &*self;
+            // Face x of a u row lies between cells x - 1 and x, usually only
+            // one of which belongs to this color. On a periodic axis the first
+            // face also borders the last cell, and the last face is left for
+            // `sync_periodic_faces`.
+            for_each_row(&mut u.data, width + 1, |y, row| {
+                for (x, u) in row.iter_mut().enumerate() {
+                    if x < width {
+                        let (d, s) = corrections[(x, y)];
+                        if s != 0.0 {
+                            *u += d * sim.s(x as i32 - 1, y as i32) / s;
+                        }
+                    }
+                    let left = match x {
+                        0 if periodic_x => Some(width - 1),
+                        0 => None,
+                        x if x == width && periodic_x => None,
+                        x => Some(x - 1),
+                    };
+                    if let Some(left) = left {
+                        let (d, s) = corrections[(left, y)];
+                        if s != 0.0 {
+                            *u -= d * sim.s(x as i32, y as i32) / s;
+                        }
+                    }
+                }
+            });
+            for_each_row(&mut v.data, width, |y, row| {
+                for (x, v) in row.iter_mut().enumerate() {
+                    if y < height {
+                        let (d, s) = corrections[(x, y)];
+                        if s != 0.0 {
+                            *v += d * sim.s(x as i32, y as i32 - 1) / s;
+                        }
+                    }
+                    let above = match y {
+                        0 if periodic_y => Some(height - 1),
+                        0 => None,
+                        y if y == height && periodic_y => None,
+                        y => Some(y - 1),
+                    };
+                    if let Some(above) = above {
+                        let (d, s) = corrections[(x, above)];
+                        if s != 0.0 {
+                            *v -= d * sim.s(x as i32, y as i32) / s;
+                        }
+                    }
+                }
+            });
+            for_each_row(&mut p.data, width, |y, row| {
+                for (x, p) in row.iter_mut().enumerate() {
+                    let (d, s) = corrections[(x, y)];
+                    if s != 0.0 {
+                        *p -= d / s * density * h / dt;
+                    }
+                }
+            });
+        }
+        self.u = u;
+        self.v = v;
+        self.p = p;
+    }
+
+    /// Solves `A q = -div(u)` for the scaled pressure `q = p * dt / (density * h)`
+    /// with MIC(0)-preconditioned conjugate gradient, then subtracts its
+    /// gradient from the face velocities.
+    pub(super) fn pcg_projection(&mut self, dt: f64) -> SolveStats {
+        let scale = self.config.density * self.config.h / dt;
+        let tolerance = self.config.solver_tolerance;
+        let norm = self.config.divergence_norm;
+        let fluid_cells = self.fluid_cells();
+        let (width, height) = (self.width, self.height);
+
+        let a = self.poisson_matrix();
+        let precon = mic_preconditioner(&a, &self.s);
+
+        let mut r = Array2D::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if self.s[(x, y)] == FLUID {
+                    r[(x, y)] = -self.divergence(x, y);
+                }
+            }
+        }
+
+        let mut q: Array2D<f64> = Array2D::new(width, height);
+        let mut stats = SolveStats {
+            iterations: 0,
+            residual: DivergenceStats::from_residual(&r.data, fluid_cells).norm(norm),
+        };
+        if stats.residual > tolerance {
+            let mut z = Array2D::new(width, height);
+            apply_preconditioner(&a, &precon, &self.s, &r, &mut z);
+            let mut search = z.clone();
+            let mut sigma = dot(&z, &r);
+
+            while stats.iterations < self.config.max_solver_iterations {
+                stats.iterations += 1;
+                apply_poisson(&a, &self.s, &search, &mut z);
+                let denom = dot(&z, &search);
+                if denom == 0.0 {
+                    break;
+                }
+                let alpha = sigma / denom;
+                for i in 0..q.data.len() {
+                    q.data[i] += alpha * search.data[i];
+                    r.data[i] -= alpha * z.data[i];
+                }
+                stats.residual = DivergenceStats::from_residual(&r.data, fluid_cells).norm(norm);
+                if stats.residual <= tolerance || stats.residual.is_nan() {
+                    break;
+                }
+
+                apply_preconditioner(&a, &precon, &self.s, &r, &mut z);
+                let sigma_new = dot(&z, &r);
+                let beta = sigma_new / sigma;
+                for i in 0..search.data.len() {
+                    search.data[i] = z.data[i] + beta * search.data[i];
+                }
+                sigma = sigma_new;
+            }
+        }
+
+        self.apply_pressure_gradient(&q);
+        for i in 0..self.p.data.len() {
+            self.p.data[i] = q.data[i] * scale;
+        }
+        stats
+    }
+
+    /// Weight of `u` face `x` in row `y` in the Poisson matrix: its open
+    /// fraction if the cells on both sides are fluid, zero otherwise.
+    pub(super) fn u_weight(&self, x: usize, y: usize) -> f64 {
+        let (xi, yi) = (x as i32, y as i32);
+        self.s(xi - 1, yi).max(0.0) * self.s(xi, yi).max(0.0) * self.u_aperture[(x, y)]
+    }
+
+    /// Weight of `v` face `y` in column `x`; see `u_weight`.
+    pub(super) fn v_weight(&self, x: usize, y: usize) -> f64 {
+        let (xi, yi) = (x as i32, y as i32);
+        self.s(xi, yi - 1).max(0.0) * self.s(xi, yi).max(0.0) * self.v_aperture[(x, y)]
+    }
+
+    fn poisson_matrix(&self) -> Poisson {
+        let (width, height) = (self.width, self.height);
+        let mut a = Poisson {
+            diag: Array2D::new(width, height),
+            plus_x: Array2D::new(width, height),
+            plus_y: Array2D::new(width, height),
+            periodic: self.periodic(),
+        };
+        for y in 0..height {
+            for x in 0..width {
+                if self.s[(x, y)] != FLUID {
+                    continue;
+                }
+                a.diag[(x, y)] = self.u_weight(x, y)
+                    + self.u_weight(x + 1, y)
+                    + self.v_weight(x, y)
+                    + self.v_weight(x, y + 1);
+                if (x + 1 < width || a.periodic.0) && self.s[((x + 1) % width, y)] == FLUID {
+                    a.plus_x[(x, y)] = -self.u_weight(x + 1, y);
+                }
+                if (y + 1 < height || a.periodic.1) && self.s[(x, (y + 1) % height)] == FLUID {
+                    a.plus_y[(x, y)] = -self.v_weight(x, y + 1);
+                }
+            }
+        }
+        a
+    }
+
+    /// Subtracts the gradient of the scaled pressure `q` from every face that
+    /// borders a fluid cell and is not closed off by a solid. Partly open faces
+    /// take the full gradient; their open fraction only weights the flux.
+    pub(super) fn apply_pressure_gradient(&mut self, q: &Array2D<f64>) {
+        let (periodic_x, periodic_y) = self.periodic();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.s[(x, y)] != FLUID {
+                    continue;
+                }
+                if self.u_weight(x, y) > 0.0 {
+                    let left = match x {
+                        0 if periodic_x => q[(self.width - 1, y)],
+                        0 => 0.0,
+                        x => q[(x - 1, y)],
+                    };
+                    self.u[(x, y)] -= q[(x, y)] - left;
+                }
+                if self.v_weight(x, y) > 0.0 {
+                    let below = match y {
+                        0 if periodic_y => q[(x, self.height - 1)],
+                        0 => 0.0,
+                        y => q[(x, y - 1)],
+                    };
+                    self.v[(x, y)] -= q[(x, y)] - below;
+                }
+                // Faces towards the far edges have no fluid cell on their other
+                // side to visit them, so handle them from this one. Periodic
+                // faces were already visited as the first face of the axis.
+                if x + 1 == self.width && !periodic_x && self.u_weight(x + 1, y) > 0.0 {
+                    self.u[(x + 1, y)] -= 0.0 - q[(x, y)];
+                }
+                if y + 1 == self.height && !periodic_y && self.v_weight(x, y + 1) > 0.0 {
+                    self.v[(x, y + 1)] -= 0.0 - q[(x, y)];
+                }
+            }
+        }
+    }
+}
+
+/// The incomplete factor only covers couplings inside the grid, so periodic
+/// wrap-around entries are left out of it.
+fn mic_preconditioner(a: &Poisson, s: &Array2D<f64>) -> Array2D<f64> {
+    let mut precon: Array2D<f64> = Array2D::new(s.width, s.height);
+    let plus_x = |x: usize, y: usize| {
+        if x + 1 < s.width {
+            a.plus_x[(x, y)]
+        } else {
+            0.0
+        }
+    };
+    let plus_y = |x: usize, y: usize| {
+        if y + 1 < s.height {
+            a.plus_y[(x, y)]
+        } else {
+            0.0
+        }
+    };
+    for y in 0..s.height {
+        for x in 0..s.width {
+            if s[(x, y)] != FLUID || a.diag[(x, y)] == 0.0 {
+                continue;
+            }
+            let mut e = a.diag[(x, y)];
+            if x > 0 {
+                let px = a.plus_x[(x - 1, y)] * precon[(x - 1, y)];
+                e -= px * px
+                    + MIC_TAU
+                        * a.plus_x[(x - 1, y)]
+                        * plus_y(x - 1, y)
+                        * precon[(x - 1, y)].powi(2);
+            }
+            if y > 0 {
+                let py = a.plus_y[(x, y - 1)] * precon[(x, y - 1)];
+                e -= py * py
+                    + MIC_TAU
+                        * a.plus_y[(x, y - 1)]
+                        * plus_x(x, y - 1)
+                        * precon[(x, y - 1)].powi(2);
+            }
+            if e < MIC_SIGMA * a.diag[(x, y)] {
+                e = a.diag[(x, y)];
+            }
+            precon[(x, y)] = 1.0 / e.sqrt();
+        }
+    }
+    precon
+}
+
+/// Computes `z = M^-1 r` with forward and backward substitution through the
+/// incomplete Cholesky factor.
+fn apply_preconditioner(
+    a: &Poisson,
+    precon: &Array2D<f64>,
+    s: &Array2D<f64>,
+    r: &Array2D<f64>,
+    z: &mut Array2D<f64>,
+) {
+    let (width, height) = (s.width, s.height);
+    z.zero();
+    for y in 0..height {
+        for x in 0..width {
+            if s[(x, y)] != FLUID {
+                continue;
+            }
+            let mut t = r[(x, y)];
+            if x > 0 {
+                t -= a.plus_x[(x - 1, y)] * precon[(x - 1, y)] * z[(x - 1, y)];
+            }
+            if y > 0 {
+                t -= a.plus_y[(x, y - 1)] * precon[(x, y - 1)] * z[(x, y - 1)];
+            }
+            z[(x, y)] = t * precon[(x, y)];
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            if s[(x, y)] != FLUID {
+                continue;
+            }
+            let mut t = z[(x, y)];
+            if x + 1 < width {
+                t -= a.plus_x[(x, y)] * precon[(x, y)] * z[(x + 1, y)];
+            }
+            if y + 1 < height {
+                t -= a.plus_y[(x, y)] * precon[(x, y)] * z[(x, y + 1)];
+            }
+            z[(x, y)] = t * precon[(x, y)];
+        }
+    }
+}
+
+fn apply_poisson(a: &Poisson, s: &Array2D<f64>, q: &Array2D<f64>, out: &mut Array2D<f64>) {
+    let (width, height) = (s.width, s.height);
+    for y in 0..height {
+        for x in 0..width {
+            if s[(x, y)] != FLUID {
+                out[(x, y)] = 0.0;
+                continue;
+            }
+            let mut sum = a.diag[(x, y)] * q[(x, y)];
+            if x > 0 {
+                sum += a.plus_x[(x - 1, y)] * q[(x - 1, y)];
+            } else if a.periodic.0 {
+                sum += a.plus_x[(width - 1, y)] * q[(width - 1, y)];
+            }
+            if x + 1 < width || a.periodic.0 {
+                sum += a.plus_x[(x, y)] * q[((x + 1) % width, y)];
+            }
+            if y > 0 {
+                sum += a.plus_y[(x, y - 1)] * q[(x, y - 1)];
+            } else if a.periodic.1 {
+                sum += a.plus_y[(x, height - 1)] * q[(x, height - 1)];
+            }
+            if y + 1 < height || a.periodic.1 {
+                sum += a.plus_y[(x, y)] * q[(x, (y + 1) % height)];
+            }
+            out[(x, y)] = sum;
+        }
+    }
+}
+
+fn dot(a: &Array2D<f64>, b: &Array2D<f64>) -> f64 {
+    a.data.iter().zip(&b.data).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Boundaries, Boundary, PressureSolver, SimConfig, SorOrdering};
+    use crate::sim::Simulation;
+    use crate::sim::testing::assert_converges;
+
+    fn conjugate_gradient(boundaries: Boundaries) -> SimConfig {
+        SimConfig::builder()
+            .pressure_solver(PressureSolver::ConjugateGradient)
+            .boundaries(boundaries)
+            .max_solver_iterations(500)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn conjugate_gradient_converges_with_walls() {
+        assert_converges(conjugate_gradient(Boundaries::default()), 60, 40);
+        assert_converges(conjugate_gradient(Boundaries::default()), 33, 47);
+    }
+
+    #[test]
+    fn conjugate_gradient_converges_with_pressure_outlet() {
+        let outlet = Boundaries {
+            right: Boundary::PressureOutlet,
+            ..Boundaries::default()
+        };
+        assert_converges(conjugate_gradient(outlet), 60, 40);
+    }
+
+    #[test]
+    fn conjugate_gradient_converges_on_periodic_axes() {
+        let channel = Boundaries {
+            left: Boundary::Periodic,
+            right: Boundary::Periodic,
+            ..Boundaries::default()
+        };
+        assert_converges(conjugate_gradient(channel), 48, 32);
+        let torus = Boundaries {
+            top: Boundary::Periodic,
+            bottom: Boundary::Periodic,
+            ..channel
+        };
+        assert_converges(conjugate_gradient(torus), 32, 32);
+    }
+
+    fn red_black(boundaries: Boundaries) -> Simulation {
+        let config = SimConfig::builder()
+            .sor_ordering(SorOrdering::RedBlack)
+            .boundaries(boundaries)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(36, 25, config);
+        sim.step();
+        sim.step();
+        sim
+    }
+
+    /// Cells of one color share no faces, so relaxing them all at once must
+    /// give exactly what relaxing them one after another does.
+    #[test]
+    fn red_black_sweep_matches_sequential_relaxation() {
+        let dt = 0.01;
+        let channel = Boundaries {
+            left: Boundary::Periodic,
+            right: Boundary::Periodic,
+            ..Boundaries::default()
+        };
+        let outlet = Boundaries {
+            right: Boundary::PressureOutlet,
+            ..Boundaries::default()
+        };
+        for boundaries in [Boundaries::default(), channel, outlet] {
+            let mut batched = red_black(boundaries);
+            let mut sequential = red_black(boundaries);
+            for color in 0..2 {
+                batched.red_black_sweep(color, dt);
+                for y in 0..sequential.height {
+                    for x in ((y + color) % 2..sequential.width).step_by(2) {
+                        sequential.relax(x, y, dt);
+                    }
+                }
+            }
+            assert_eq!(batched.u.data, sequential.u.data, "{:?}", boundaries);
+            assert_eq!(batched.v.data, sequential.v.data, "{:?}", boundaries);
+            assert_eq!(batched.p.data, sequential.p.data, "{:?}", boundaries);
+        }
+    }
+
+    #[test]
+    fn red_black_converges_to_lexicographic_solution() {
+        let velocities = |ordering| {
+            let config = SimConfig::builder()
+                .sor_ordering(ordering)
+                .sor_until_converged(true)
+                .projection_iterations(5000)
+                .solver_tolerance(1e-8)
+                .build()
+                .unwrap();
+            let mut sim = Simulation::new(36, 25, config);
+            let stats = sim.step();
+            assert!(stats.solve.residual <= 1e-8, "{:?}", stats.solve);
+            (sim.u.data, sim.v.data)
+        };
+        let (u, v) = velocities(SorOrdering::Lexicographic);
+        let (rb_u, rb_v) = velocities(SorOrdering::RedBlack);
+        let difference = u
+            .iter()
+            .zip(&rb_u)
+            .chain(v.iter().zip(&rb_v))
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        assert!(difference < 1e-6, "{}", difference);
+    }
+}
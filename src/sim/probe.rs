@@ -0,0 +1,38 @@
+use super::Simulation;
+use super::interpolation::Grid;
+
+/// A named point, in the cell units of `Shape`, where the headless runner
+/// records the flow after every step.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct Probe {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Flow quantities interpolated at one point.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProbeSample {
+    pub u: f64,
+    pub v: f64,
+    pub pressure: f64,
+    pub smoke: f64,
+    pub temperature: f64,
+}
+
+impl Simulation {
+    /// Samples the flow at `(x, y)` in cell units with the configured
+    /// interpolation.
+    pub fn probe(&self, x: f64, y: f64) -> ProbeSample {
+        let (x, y) = (x * self.config.h, y * self.config.h);
+        let (u, v) = self.sample_velocity(x, y);
+        ProbeSample {
+            u,
+            v,
+            pressure: self.sample(&self.p, Grid::Cell, x, y),
+            smoke: self.sample(&self.smoke, Grid::Cell, x, y),
+            temperature: self.sample(&self.temperature, Grid::Cell, x, y),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use super::Simulation;
+use super::interpolation::Grid;
+use crate::config::ConfigError;
+use crate::util::Array2D;
+
+/// Region of a scalar channel held at a fixed value every step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ScalarSource {
+    /// Rows `start..end` of the inflow column, like the smoke bands.
+    Inflow {
+        start: usize,
+        end: usize,
+        value: f64,
+    },
+    /// A disc of cells anywhere in the domain.
+    Circle {
+        x: i32,
+        y: i32,
+        radius: f32,
+        value: f64,
+    },
+}
+
+/// A named passive scalar carried by the flow alongside `smoke`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct ScalarChannel {
+    pub name: String,
+    #[cfg_attr(feature = "scene", serde(default))]
+    pub sources: Vec<ScalarSource>,
+    /// Diffusivity of the channel; zero leaves it purely advected.
+    #[cfg_attr(feature = "scene", serde(default))]
+    pub diffusivity: f64,
+    /// Exponential decay rate per unit of simulated time.
+    #[cfg_attr(feature = "scene", serde(default))]
+    pub decay: f64,
+    /// Color the viewer draws the channel in.
+    pub color: [u8; 3],
+    #[cfg_attr(feature = "scene", serde(skip))]
+    values: Array2D<f64>,
+}
+
+impl ScalarChannel {
+    pub fn new(name: impl Into<String>, color: [u8; 3]) -> Self {
+        ScalarChannel {
+            name: name.into(),
+            sources: Vec::new(),
+            diffusivity: 0.0,
+            decay: 0.0,
+            color,
+            values: Array2D::new(0, 0),
+        }
+    }
+
+    pub fn with_source(mut self, source: ScalarSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn with_diffusivity(mut self, diffusivity: f64) -> Self {
+        self.diffusivity = diffusivity;
+        self
+    }
+
+    pub fn with_decay(mut self, decay: f64) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Checks that `diffusivity` and `decay` are finite and non-negative.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (field, value) in [("diffusivity", self.diffusivity), ("decay", self.decay)] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(ConfigError::OutOfRange {
+                    field,
+                    value,
+                    expected: "finite and non-negative",
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Concentration in each cell, in row-major order.
+    pub fn values(&self) -> &[f64] {
+        &self.values.data
+    }
+
+    /// Holds every source at its value; circles wrap around the axes that
+    /// have a `period`, as in `Array2D::fill_circle_wrapping`.
+    fn apply_sources(&mut self, period: (Option<usize>, Option<usize>)) {
+        for source in &self.sources {
+            match *source {
+                ScalarSource::Inflow { start, end, value } => {
+                    for y in start..end.min(self.values.height) {
+                        self.values[(0, y)] = value;
+                    }
+                }
+                ScalarSource::Circle {
+                    x,
+                    y,
+                    radius,
+                    value,
+                } => self
+                    .values
+                    .fill_circle_wrapping(x, y, radius, value, period),
+            }
+        }
+    }
+}
+
+impl Simulation {
+    /// Adds a channel, starting empty apart from its sources, and returns its
+    /// index. Fails with the error from `ScalarChannel::validate`.
+    pub fn add_channel(&mut self, channel: ScalarChannel) -> Result<usize, ConfigError> {
+        channel.validate()?;
+        self.insert_channel(channel);
+        Ok(self.channels.len() - 1)
+    }
+
+    /// `add_channel` for a channel that has already been validated.
+    pub(super) fn insert_channel(&mut self, mut channel: ScalarChannel) {
+        channel.values = Array2D::new(self.width, self.height);
+        channel.apply_sources(self.periods());
+        self.channels.push(channel);
+    }
+
+    pub fn channels(&self) -> &[ScalarChannel] {
+        &self.channels
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&ScalarChannel> {
+        self.channels.iter().find(|channel| channel.name == name)
+    }
+
+    pub fn clear_channels(&mut self) {
+        self.channels.clear();
+    }
+
+    /// Refreshes the sources of every channel, then advects, diffuses and
+    /// decays it.
+    pub(super) fn channel_advection(&mut self, dt: f64) {
+        let mut channels = std::mem::take(&mut self.channels);
+        for channel in &mut channels {
+            channel.apply_sources(self.periods());
+            channel.values = self.advect(&channel.values, Grid::Cell, dt);
+            if channel.diffusivity > 0.0 {
+                let alpha = channel.diffusivity * dt / (self.config.h * self.config.h);
+                channel.values = self.diffuse(&channel.values, Grid::Cell, alpha);
+            }
+            if channel.decay > 0.0 {
+                let factor = (-channel.decay * dt).exp();
+                channel.values.data.iter_mut().for_each(|c| *c *= factor);
+            }
+        }
+        self.channels = channels;
+    }
+
+    /// Zeroes every channel inside a disc, e.g. where an obstacle was drawn.
+    pub(super) fn clear_channels_in(&mut self, center_x: i32, center_y: i32, radius: f32) {
+        let period = self.periods();
+        for channel in &mut self.channels {
+            channel
+                .values
+                .fill_circle_wrapping(center_x, center_y, radius, 0.0, period);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScalarChannel, ScalarSource};
+    use crate::config::{Boundaries, Boundary, ConfigError, SimConfig};
+    use crate::sim::Simulation;
+
+    #[test]
+    fn rejects_negative_and_nan_rates() {
+        let mut sim = Simulation::new(20, 10, SimConfig::default());
+        for channel in [
+            ScalarChannel::new("dye", [255, 0, 0]).with_diffusivity(-1.0),
+            ScalarChannel::new("dye", [255, 0, 0]).with_diffusivity(f64::NAN),
+            ScalarChannel::new("dye", [255, 0, 0]).with_decay(f64::INFINITY),
+            ScalarChannel::new("dye", [255, 0, 0]).with_decay(-0.5),
+        ] {
+            assert!(matches!(
+                sim.add_channel(channel),
+                Err(ConfigError::OutOfRange { .. })
+            ));
+        }
+        assert!(sim.channels().is_empty());
+        let channel = ScalarChannel::new("dye", [255, 0, 0])
+            .with_diffusivity(0.1)
+            .with_decay(0.2);
+        assert_eq!(sim.add_channel(channel), Ok(0));
+    }
+
+    #[test]
+    fn circle_sources_wrap_around_periodic_edges() {
+        let config = SimConfig::builder()
+            .boundaries(Boundaries {
+                left: Boundary::Periodic,
+                right: Boundary::Periodic,
+                ..Boundaries::default()
+            })
+            .draw_obstacle(false)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(20, 10, config);
+        let source = ScalarSource::Circle {
+            x: 0,
+            y: 5,
+            radius: 2.0,
+            value: 1.0,
+        };
+        sim.add_channel(ScalarChannel::new("dye", [255, 0, 0]).with_source(source))
+            .unwrap();
+        let values = &sim.channels()[0].values;
+        assert_eq!(values[(0, 5)], 1.0);
+        assert_eq!(values[(18, 5)], 1.0);
+        assert_eq!(values[(19, 4)], 1.0);
+        assert_eq!(values[(17, 5)], 0.0);
+    }
+
+    #[test]
+    fn channels_are_carried_downstream_and_decay() {
+        let config = SimConfig::builder().draw_obstacle(false).build().unwrap();
+        let mut sim = Simulation::new(40, 20, config);
+        let source = ScalarSource::Inflow {
+            start: 5,
+            end: 15,
+            value: 1.0,
+        };
+        let dye = ScalarChannel::new("dye", [255, 0, 0]).with_source(source);
+        sim.add_channel(dye.clone()).unwrap();
+        sim.add_channel(dye.with_decay(0.5)).unwrap();
+        for _ in 0..10 {
+            sim.step();
+        }
+        let (kept, decayed) = (&sim.channels()[0].values, &sim.channels()[1].values);
+        assert!(kept[(30, 10)] > 0.9);
+        assert!(kept[(30, 2)] < 0.01 && kept[(30, 18)] < 0.01);
+        assert!(decayed[(30, 10)] > 0.3 && decayed[(30, 10)] < 0.8 * kept[(30, 10)]);
+        assert!(decayed[(30, 10)] < decayed[(10, 10)]);
+    }
+}
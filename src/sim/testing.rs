@@ -0,0 +1,37 @@
+//! Helpers shared by the tests of several modules.
+
+use super::Simulation;
+use crate::config::SimConfig;
+
+/// Steps a `width` by `height` grid a few times with `config`, typically
+/// picking the pressure solver under test, and checks that every solve
+/// converges within `max_solver_iterations` and that the divergence measured
+/// on the projected velocities is within tolerance.
+pub(crate) fn assert_converges(config: SimConfig, width: usize, height: usize) {
+    let (tolerance, norm) = (config.solver_tolerance, config.divergence_norm);
+    let max_iterations = config.max_solver_iterations;
+    let solver = config.pressure_solver;
+    let mut sim = Simulation::new(width, height, config);
+    for _ in 0..3 {
+        let stats = sim.step();
+        assert!(
+            stats.solve.residual <= tolerance && stats.solve.iterations < max_iterations,
+            "{}x{} {:?}: {:?}",
+            width,
+            height,
+            solver,
+            stats.solve
+        );
+        let divergence = stats.divergence_after.norm(norm);
+        assert!(divergence <= tolerance * (1.0 + 1e-9), "{}", divergence);
+    }
+}
+
+/// Overrelaxation past the range SOR converges in, which the builder would
+/// reject; the pressure overflows to NaN within a few steps.
+pub(crate) fn unstable_config() -> SimConfig {
+    SimConfig {
+        overrelaxation: 2.5,
+        ..SimConfig::default()
+    }
+}
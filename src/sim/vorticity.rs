@@ -0,0 +1,154 @@
+use super::{FLUID, Simulation};
+use crate::util::Array2D;
+
+impl Simulation {
+    /// Updates `curl` with the vorticity `dv/dx - du/dy` at each cell centre,
+    /// using central differences between fluid cells. Solid cells and the
+    /// border cells of axes that do not wrap are left at zero.
+    pub(super) fn compute_curl(&mut self) {
+        let h = self.config.h;
+        let (periodic_x, periodic_y) = self.periodic();
+        let mut curl = Array2D::new(self.width, self.height);
+        for y in 0..self.height {
+            let Some((up, down)) = neighbours(y, self.height, periodic_y) else {
+                continue;
+            };
+            for x in 0..self.width {
+                let Some((left, right)) = neighbours(x, self.width, periodic_x) else {
+                    continue;
+                };
+                if self.s[(x, y)] != FLUID {
+                    continue;
+                }
+                let dv_dx = self.center_v(right, y) - self.center_v(left, y);
+                let du_dy = self.center_u(x, down) - self.center_u(x, up);
+                curl[(x, y)] = (dv_dx - du_dy) / (2.0 * h);
+            }
+        }
+        self.curl = curl;
+    }
+
+    /// Adds the confinement force `eps * h * (N x curl)`, where `N` points
+    /// towards increasing vorticity magnitude, to every open face.
+    pub(super) fn vorticity_confinement(&mut self, dt: f64) {
+        let h = self.config.h;
+        let strength = self.config.vorticity_confinement;
+        let (periodic_x, periodic_y) = self.periodic();
+        let mut fx = Array2D::new(self.width, self.height);
+        let mut fy = Array2D::new(self.width, self.height);
+        for y in 0..self.height {
+            let Some((up, down)) = neighbours(y, self.height, periodic_y) else {
+                continue;
+            };
+            for x in 0..self.width {
+                let Some((left, right)) = neighbours(x, self.width, periodic_x) else {
+                    continue;
+                };
+                if self.s[(x, y)] != FLUID {
+                    continue;
+                }
+                let gx = (self.curl[(right, y)].abs() - self.curl[(left, y)].abs()) / (2.0 * h);
+                let gy = (self.curl[(x, down)].abs() - self.curl[(x, up)].abs()) / (2.0 * h);
+                let length = (gx * gx + gy * gy).sqrt();
+                if length < 1e-12 {
+                    continue;
+                }
+                let (nx, ny) = (gx / length, gy / length);
+                let w = self.curl[(x, y)];
+                fx[(x, y)] = strength * h * ny * w;
+                fy[(x, y)] = -strength * h * nx * w;
+            }
+        }
+
+        // The first face of a periodic axis sits between the last cell and
+        // the first; `sync_periodic_faces` copies it to the last face.
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let Some(left) = x.checked_sub(1).or(periodic_x.then(|| self.width - 1)) else {
+                    continue;
+                };
+                if self.open_u(x, y) {
+                    self.u[(x, y)] += dt * 0.5 * (fx[(left, y)] + fx[(x, y)]);
+                }
+            }
+        }
+        for y in 0..self.height {
+            let Some(up) = y.checked_sub(1).or(periodic_y.then(|| self.height - 1)) else {
+                continue;
+            };
+            for x in 0..self.width {
+                if self.open_v(x, y) {
+                    self.v[(x, y)] += dt * 0.5 * (fy[(x, up)] + fy[(x, y)]);
+                }
+            }
+        }
+    }
+
+    fn center_u(&self, x: usize, y: usize) -> f64 {
+        0.5 * (self.u[(x, y)] + self.u[(self.wrap_u(x + 1), y)])
+    }
+
+    fn center_v(&self, x: usize, y: usize) -> f64 {
+        0.5 * (self.v[(x, y)] + self.v[(x, self.wrap_v(y + 1))])
+    }
+}
+
+/// Cells either side of `i` along an axis of `len` cells, wrapping around a
+/// periodic axis. `None` for the border cells of an axis that does not wrap.
+fn neighbours(i: usize, len: usize, periodic: bool) -> Option<(usize, usize)> {
+    if periodic {
+        Some(((i + len - 1) % len, (i + 1) % len))
+    } else if i > 0 && i + 1 < len {
+        Some((i - 1, i + 1))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Boundaries, Boundary, SimConfig};
+    use crate::sim::Simulation;
+    use std::f64::consts::PI;
+
+    /// Curl of a doubly periodic flow shifted by `shift` cells.
+    fn torus_curl(shift: (usize, usize)) -> Simulation {
+        let config = SimConfig::builder()
+            .boundaries(Boundaries {
+                left: Boundary::Periodic,
+                right: Boundary::Periodic,
+                top: Boundary::Periodic,
+                bottom: Boundary::Periodic,
+            })
+            .draw_obstacle(false)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(16, 12, config);
+        let h = sim.config.h;
+        let (width, height) = (16.0 * h, 12.0 * h);
+        let (dx, dy) = (shift.0 as f64 * h, shift.1 as f64 * h);
+        sim.set_velocity(|x, y| {
+            let (x, y) = (2.0 * PI * (x - dx) / width, 2.0 * PI * (y - dy) / height);
+            ((x + y).sin(), x.cos() * y.sin())
+        });
+        sim.compute_curl();
+        sim
+    }
+
+    #[test]
+    fn curl_on_a_torus_is_translation_invariant() {
+        let still = torus_curl((0, 0));
+        let moved = torus_curl((5, 7));
+        for y in 0..12 {
+            for x in 0..16 {
+                let expected = still.curl[((x + 16 - 5) % 16, (y + 12 - 7) % 12)];
+                let curl = moved.curl[(x, y)];
+                assert!(
+                    (curl - expected).abs() < 1e-9,
+                    "({x}, {y}): {curl} vs {expected}"
+                );
+            }
+        }
+        assert!(still.curl[(0, 0)] != 0.0);
+    }
+}
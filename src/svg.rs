@@ -0,0 +1,737 @@
+//! Obstacle outlines read from SVG files, flattened into `Shape` polygons.
+//!
+//! Only the geometry of `path`, `polygon`, `polyline`, `rect`, `circle` and
+//! `ellipse` elements is read. Styles, `transform` attributes and `defs` are
+//! ignored, so outlines should be exported with transforms applied.
+
+use crate::shape::Shape;
+use std::f64::consts::PI;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Straight segments each Bézier curve is split into.
+const CURVE_SEGMENTS: usize = 16;
+/// Largest angle one segment of a flattened arc or circle turns through.
+const ARC_STEP: f64 = PI / 32.0;
+
+/// One SVG element as closed rings of points, in SVG user units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgPath {
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+/// Every outline found in an SVG document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Svg {
+    pub paths: Vec<SvgPath>,
+}
+
+#[derive(Debug)]
+pub enum SvgError {
+    Io(io::Error),
+    /// Path data or an attribute that could not be parsed.
+    Syntax(String),
+}
+
+impl Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgError::Io(e) => write!(f, "{}", e),
+            SvgError::Syntax(message) => write!(f, "invalid SVG: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SvgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SvgError::Io(e) => Some(e),
+            SvgError::Syntax(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SvgError {
+    fn from(e: io::Error) -> Self {
+        SvgError::Io(e)
+    }
+}
+
+impl SvgPath {
+    /// Parses the `d` attribute of a `path` element. Curves and arcs are
+    /// flattened, and every subpath is treated as closed.
+    pub fn parse(data: &str) -> Result<Self, SvgError> {
+        PathParser::new(data).parse()
+    }
+
+    /// Closed polygon through `points`, as in a `polygon` element.
+    pub fn polygon(points: Vec<(f64, f64)>) -> Self {
+        SvgPath {
+            rings: vec![points],
+        }
+    }
+
+    fn ellipse(center: (f64, f64), radii: (f64, f64)) -> Self {
+        let count = (2.0 * PI / ARC_STEP) as usize;
+        let points = (0..count)
+            .map(|k| {
+                let angle = 2.0 * PI * k as f64 / count as f64;
+                (
+                    center.0 + radii.0 * angle.cos(),
+                    center.1 + radii.1 * angle.sin(),
+                )
+            })
+            .collect();
+        SvgPath::polygon(points)
+    }
+
+    fn bounds(&self) -> (f64, f64, f64, f64) {
+        self.rings.iter().flatten().fold(EMPTY, |b, &(x, y)| {
+            (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y))
+        })
+    }
+
+    /// The outline mapped to cell units by `point * scale + offset`, filled
+    /// with the even-odd rule so inner rings cut holes.
+    pub fn shape(&self, scale: f64, offset: (f64, f64)) -> Shape {
+        // Each extra ring hangs off the first ring's start by a bridge that
+        // is walked out and back, so every ray crosses it an even number of
+        // times and the fill is unchanged.
+        let mut rings = self.rings.iter().filter(|ring| ring.len() >= 3);
+        let mut points = Vec::new();
+        if let Some(first) = rings.next() {
+            points.extend(first.iter().chain(first.first()));
+            for ring in rings {
+                points.extend(ring.iter().chain(ring.first()).chain(first.first()));
+            }
+        }
+        let points = points
+            .into_iter()
+            .map(|&(x, y)| (x * scale + offset.0, y * scale + offset.1))
+            .collect();
+        Shape::polygon(points)
+    }
+}
+
+impl Svg {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SvgError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Collects the outlines of a whole SVG document.
+    pub fn parse(document: &str) -> Result<Self, SvgError> {
+        let mut paths = Vec::new();
+        let mut rest = document;
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+            if let Some(comment) = rest.strip_prefix("!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            let end = rest
+                .find('>')
+                .ok_or_else(|| SvgError::Syntax("unterminated tag".to_string()))?;
+            let tag = &rest[..end];
+            rest = &rest[end + 1..];
+            let name_end = tag
+                .find(|c: char| c.is_whitespace() || c == '/')
+                .unwrap_or(tag.len());
+            let (name, attributes) = tag.split_at(name_end);
+            if let Some(path) = element(name, &Attributes(attributes))? {
+                paths.push(path);
+            }
+        }
+        Ok(Svg { paths })
+    }
+
+    /// Box `(min_x, min_y, max_x, max_y)` around every outline, in SVG units.
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        self.paths.iter().map(SvgPath::bounds).fold(EMPTY, |a, b| {
+            (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+        })
+    }
+
+    /// All outlines mapped to cell units by `point * scale + offset`.
+    pub fn shape(&self, scale: f64, offset: (f64, f64)) -> Shape {
+        Shape::union(
+            self.paths
+                .iter()
+                .map(|path| path.shape(scale, offset))
+                .collect(),
+        )
+    }
+
+    /// All outlines scaled uniformly to the largest size that fits between
+    /// `min` and `max` in cell units, and centred there. Outlines that are
+    /// empty or shrink to a single point give an empty shape.
+    pub fn fit(&self, min: (f64, f64), max: (f64, f64)) -> Shape {
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let (width, height) = (max_x - min_x, max_y - min_y);
+        if min_x > max_x || (width == 0.0 && height == 0.0) {
+            return Shape::union(Vec::new());
+        }
+        let scale = ((max.0 - min.0) / width).min((max.1 - min.1) / height);
+        let offset = (
+            (min.0 + max.0 - (min_x + max_x) * scale) / 2.0,
+            (min.1 + max.1 - (min_y + max_y) * scale) / 2.0,
+        );
+        self.shape(scale, offset)
+    }
+}
+
+const EMPTY: (f64, f64, f64, f64) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+
+/// Outline of the element `name`, or `None` for elements without geometry.
+fn element(name: &str, attributes: &Attributes) -> Result<Option<SvgPath>, SvgError> {
+    let path = match name {
+        "path" => SvgPath::parse(attributes.get("d").unwrap_or(""))?,
+        "polygon" | "polyline" => {
+            let points = attributes.get("points").unwrap_or("");
+            let mut numbers = Numbers(points);
+            let mut ring = Vec::new();
+            while !numbers.at_end() {
+                ring.push((numbers.number()?, numbers.number()?));
+            }
+            SvgPath::polygon(ring)
+        }
+        "rect" => {
+            let (x, y) = (attributes.number("x")?, attributes.number("y")?);
+            let (w, h) = (attributes.number("width")?, attributes.number("height")?);
+            SvgPath::polygon(vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)])
+        }
+        "circle" => {
+            let r = attributes.number("r")?;
+            SvgPath::ellipse((attributes.number("cx")?, attributes.number("cy")?), (r, r))
+        }
+        "ellipse" => SvgPath::ellipse(
+            (attributes.number("cx")?, attributes.number("cy")?),
+            (attributes.number("rx")?, attributes.number("ry")?),
+        ),
+        _ => return Ok(None),
+    };
+    Ok(Some(path))
+}
+
+/// The attribute text of a tag.
+struct Attributes<'a>(&'a str);
+
+impl<'a> Attributes<'a> {
+    fn get(&self, name: &str) -> Option<&'a str> {
+        let mut rest = self.0;
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq].trim();
+            let value = rest[eq + 1..].trim_start();
+            let quote = value.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+            let end = value[1..].find(quote)? + 1;
+            if key == name {
+                return Some(&value[1..end]);
+            }
+            rest = &value[end + 1..];
+        }
+        None
+    }
+
+    /// A numeric attribute, zero when absent as SVG specifies.
+    fn number(&self, name: &str) -> Result<f64, SvgError> {
+        match self.get(name) {
+            Some(value) => {
+                let value = value.trim().trim_end_matches("px");
+                value.parse().map_err(|_| {
+                    SvgError::Syntax(format!("{}=\"{}\" is not a number", name, value))
+                })
+            }
+            None => Ok(0.0),
+        }
+    }
+}
+
+/// Numbers in path data or a `points` list, which may be separated by
+/// whitespace, commas or nothing at all, as in `"1.5.5-2"`.
+struct Numbers<'a>(&'a str);
+
+impl Numbers<'_> {
+    fn skip_separators(&mut self) {
+        self.0 = self
+            .0
+            .trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.0.is_empty()
+    }
+
+    /// Whether a number, rather than a command letter, comes next.
+    fn at_number(&mut self) -> bool {
+        self.skip_separators();
+        self.0
+            .starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+    }
+
+    fn number(&mut self) -> Result<f64, SvgError> {
+        self.skip_separators();
+        let bytes = self.0.as_bytes();
+        let mut end = 0;
+        if matches!(bytes.first(), Some(b'-' | b'+')) {
+            end += 1;
+        }
+        let mut seen_dot = false;
+        while let Some(&b) = bytes.get(end) {
+            if b == b'.' && !seen_dot {
+                seen_dot = true;
+            } else if !b.is_ascii_digit() {
+                break;
+            }
+            end += 1;
+        }
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            let mut exponent = end + 1;
+            if matches!(bytes.get(exponent), Some(b'-' | b'+')) {
+                exponent += 1;
+            }
+            if bytes.get(exponent).is_some_and(u8::is_ascii_digit) {
+                end = exponent;
+                while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+                    end += 1;
+                }
+            }
+        }
+        let number = self.0[..end].parse().map_err(|_| {
+            let found: String = self.0.chars().take(10).collect();
+            SvgError::Syntax(format!("expected a number at '{}'", found))
+        })?;
+        self.0 = &self.0[end..];
+        Ok(number)
+    }
+
+    /// An arc flag, which is a single `0` or `1` that may run into the next
+    /// number.
+    fn flag(&mut self) -> Result<bool, SvgError> {
+        self.skip_separators();
+        let flag = match self.0.as_bytes().first() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(SvgError::Syntax("expected an arc flag".to_string())),
+        };
+        self.0 = &self.0[1..];
+        Ok(flag)
+    }
+}
+
+struct PathParser<'a> {
+    numbers: Numbers<'a>,
+    rings: Vec<Vec<(f64, f64)>>,
+    ring: Vec<(f64, f64)>,
+    current: (f64, f64),
+    /// Second control point of the previous curve, reflected by `S` and `T`.
+    control: Option<(f64, f64)>,
+}
+
+impl<'a> PathParser<'a> {
+    fn new(data: &'a str) -> Self {
+        PathParser {
+            numbers: Numbers(data),
+            rings: Vec::new(),
+            ring: Vec::new(),
+            current: (0.0, 0.0),
+            control: None,
+        }
+    }
+
+    fn parse(mut self) -> Result<SvgPath, SvgError> {
+        let mut command = None;
+        while !self.numbers.at_end() {
+            if !self.numbers.at_number() {
+                let letter = self.numbers.0.chars().next().unwrap();
+                self.numbers.0 = &self.numbers.0[letter.len_utf8()..];
+                if self.rings.is_empty() && self.ring.is_empty() && !matches!(letter, 'M' | 'm') {
+                    return Err(SvgError::Syntax("path data must start with M".to_string()));
+                }
+                command = Some(letter);
+                if letter == 'Z' || letter == 'z' {
+                    self.close();
+                    command = None;
+                    continue;
+                }
+            }
+            let letter = command
+                .ok_or_else(|| SvgError::Syntax("path data must start with M".to_string()))?;
+            self.segment(letter)?;
+            // Coordinates after a moveto are implicit linetos.
+            command = match letter {
+                'M' => Some('L'),
+                'm' => Some('l'),
+                _ => command,
+            };
+        }
+        self.close();
+        Ok(SvgPath { rings: self.rings })
+    }
+
+    fn segment(&mut self, letter: char) -> Result<(), SvgError> {
+        let relative = letter.is_ascii_lowercase();
+        let origin = if relative { self.current } else { (0.0, 0.0) };
+        let point = |numbers: &mut Numbers| -> Result<(f64, f64), SvgError> {
+            Ok((origin.0 + numbers.number()?, origin.1 + numbers.number()?))
+        };
+        let mut control = None;
+        match letter.to_ascii_uppercase() {
+            'M' => {
+                let to = point(&mut self.numbers)?;
+                self.close();
+                self.current = to;
+                self.ring.push(to);
+            }
+            'L' => {
+                let to = point(&mut self.numbers)?;
+                self.line_to(to);
+            }
+            'H' => {
+                let x = origin.0 + self.numbers.number()?;
+                self.line_to((x, self.current.1));
+            }
+            'V' => {
+                let y = origin.1 + self.numbers.number()?;
+                self.line_to((self.current.0, y));
+            }
+            'C' | 'S' => {
+                let c1 = if letter.eq_ignore_ascii_case(&'C') {
+                    point(&mut self.numbers)?
+                } else {
+                    self.reflected_control()
+                };
+                let c2 = point(&mut self.numbers)?;
+                let to = point(&mut self.numbers)?;
+                let from = self.current;
+                self.curve_to(to, |t| {
+                    let s = 1.0 - t;
+                    let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+                    (
+                        a * from.0 + b * c1.0 + c * c2.0 + d * to.0,
+                        a * from.1 + b * c1.1 + c * c2.1 + d * to.1,
+                    )
+                });
+                control = Some(c2);
+            }
+            'Q' | 'T' => {
+                let c = if letter.eq_ignore_ascii_case(&'Q') {
+                    point(&mut self.numbers)?
+                } else {
+                    self.reflected_control()
+                };
+                let to = point(&mut self.numbers)?;
+                let from = self.current;
+                self.curve_to(to, |t| {
+                    let s = 1.0 - t;
+                    (
+                        s * s * from.0 + 2.0 * s * t * c.0 + t * t * to.0,
+                        s * s * from.1 + 2.0 * s * t * c.1 + t * t * to.1,
+                    )
+                });
+                control = Some(c);
+            }
+            'A' => {
+                let radii = (self.numbers.number()?.abs(), self.numbers.number()?.abs());
+                let rotation = self.numbers.number()?.to_radians();
+                let large_arc = self.numbers.flag()?;
+                let sweep = self.numbers.flag()?;
+                let to = point(&mut self.numbers)?;
+                self.arc_to(radii, rotation, large_arc, sweep, to);
+            }
+            _ => {
+                return Err(SvgError::Syntax(format!(
+                    "unknown path command '{}'",
+                    letter
+                )));
+            }
+        }
+        self.control = control;
+        Ok(())
+    }
+
+    /// First control point of a smooth curve: the previous curve's last
+    /// control point reflected through the current point.
+    fn reflected_control(&self) -> (f64, f64) {
+        match self.control {
+            Some(c) => (2.0 * self.current.0 - c.0, 2.0 * self.current.1 - c.1),
+            None => self.current,
+        }
+    }
+
+    fn line_to(&mut self, to: (f64, f64)) {
+        if self.ring.is_empty() {
+            self.ring.push(self.current);
+        }
+        self.ring.push(to);
+        self.current = to;
+    }
+
+    fn curve_to(&mut self, to: (f64, f64), at: impl Fn(f64) -> (f64, f64)) {
+        for k in 1..CURVE_SEGMENTS {
+            self.line_to(at(k as f64 / CURVE_SEGMENTS as f64));
+        }
+        self.line_to(to);
+    }
+
+    /// Elliptical arc from the current point, converted to centre form as in
+    /// the SVG specification's implementation notes.
+    fn arc_to(
+        &mut self,
+        radii: (f64, f64),
+        rotation: f64,
+        large_arc: bool,
+        sweep: bool,
+        to: (f64, f64),
+    ) {
+        let from = self.current;
+        if radii.0 == 0.0 || radii.1 == 0.0 || from == to {
+            self.line_to(to);
+            return;
+        }
+        let (sin, cos) = rotation.sin_cos();
+        let (hx, hy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+        let (x1, y1) = (cos * hx + sin * hy, -sin * hx + cos * hy);
+
+        // Radii too small to span the endpoints are scaled up until they do.
+        let (mut rx, mut ry) = radii;
+        let excess = (x1 / rx).powi(2) + (y1 / ry).powi(2);
+        if excess > 1.0 {
+            rx *= excess.sqrt();
+            ry *= excess.sqrt();
+        }
+        let numerator = (rx * ry).powi(2) - (rx * y1).powi(2) - (ry * x1).powi(2);
+        let denominator = (rx * y1).powi(2) + (ry * x1).powi(2);
+        let mut root = (numerator / denominator).max(0.0).sqrt();
+        if large_arc == sweep {
+            root = -root;
+        }
+        let (cx1, cy1) = (root * rx * y1 / ry, -root * ry * x1 / rx);
+        let center = (
+            cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.0,
+            sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.0,
+        );
+
+        let start = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+        let end = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx);
+        let mut delta = end - start;
+        if sweep && delta < 0.0 {
+            delta += 2.0 * PI;
+        } else if !sweep && delta > 0.0 {
+            delta -= 2.0 * PI;
+        }
+
+        let steps = ((delta.abs() / ARC_STEP).ceil() as usize).max(1);
+        for k in 1..steps {
+            let angle = start + delta * k as f64 / steps as f64;
+            let (x, y) = (rx * angle.cos(), ry * angle.sin());
+            self.line_to((center.0 + cos * x - sin * y, center.1 + sin * x + cos * y));
+        }
+        self.line_to(to);
+    }
+
+    /// Ends the current subpath, returning to its start as `Z` does.
+    fn close(&mut self) {
+        if let Some(&start) = self.ring.first() {
+            self.current = start;
+        }
+        let ring = std::mem::take(&mut self.ring);
+        if ring.len() >= 3 {
+            self.rings.push(ring);
+        }
+        self.control = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CURVE_SEGMENTS, Svg, SvgError, SvgPath};
+    use crate::shape::Shape;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    fn single_ring(data: &str) -> Vec<(f64, f64)> {
+        let mut path = SvgPath::parse(data).unwrap();
+        assert_eq!(path.rings.len(), 1, "{}", data);
+        path.rings.pop().unwrap()
+    }
+
+    #[test]
+    fn parses_lines_in_absolute_and_relative_form() {
+        assert_eq!(
+            single_ring("M10 20 30 40 l5-5 H0 V0z"),
+            [
+                (10.0, 20.0),
+                (30.0, 40.0),
+                (35.0, 35.0),
+                (0.0, 35.0),
+                (0.0, 0.0)
+            ]
+        );
+        assert_eq!(
+            single_ring("m1.5.5-2e1,3 1E1 0h-1v+2"),
+            [
+                (1.5, 0.5),
+                (-18.5, 3.5),
+                (-8.5, 3.5),
+                (-9.5, 3.5),
+                (-9.5, 5.5)
+            ]
+        );
+        // Each subpath starts where `z` returned to unless `m` moves on.
+        let path = SvgPath::parse("M0 0h2v2z m1 1h2v2z M9 9").unwrap();
+        assert_eq!(path.rings.len(), 2);
+        assert_eq!(path.rings[1][0], (1.0, 1.0));
+    }
+
+    #[test]
+    fn flattens_curves_through_their_control_points() {
+        let ring = single_ring("M0 0 C0 10 10 10 10 0 S20 -10 20 0");
+        let half = CURVE_SEGMENTS / 2;
+        assert!(close(ring[half], (5.0, 7.5)));
+        // `S` reflects the previous control point through the join.
+        assert!(close(ring[CURVE_SEGMENTS + half], (15.0, -7.5)));
+        assert_eq!(*ring.last().unwrap(), (20.0, 0.0));
+
+        let ring = single_ring("M0 0 Q5 10 10 0 T20 0");
+        assert!(close(ring[half], (5.0, 5.0)));
+        assert!(close(ring[CURVE_SEGMENTS + half], (15.0, -5.0)));
+    }
+
+    #[test]
+    fn arcs_follow_their_flags() {
+        let on_circle = |ring: &[(f64, f64)], center: (f64, f64), radius: f64| {
+            ring.iter()
+                .all(|&(x, y)| ((x - center.0).hypot(y - center.1) - radius).abs() < 1e-9)
+        };
+        // A half circle, with the radius given too small and scaled up.
+        for data in ["M0 0 A10 10 0 0 1 20 0", "M0 0 A1 1 0 0 1 20 0"] {
+            let ring = single_ring(data);
+            assert!(on_circle(&ring, (10.0, 0.0), 10.0), "{}", data);
+            assert!(ring.iter().all(|&(_, y)| y <= 1e-9), "{}", data);
+        }
+        let ring = single_ring("M0 0 A10 10 0 0 0 20 0");
+        assert!(ring.iter().all(|&(_, y)| y >= -1e-9));
+
+        // Between two points on a circle the four flag pairs pick each
+        // centre's short and long way round.
+        let span = |ring: &[(f64, f64)]| {
+            let xs = ring.iter().map(|p| p.0);
+            xs.clone().fold(f64::MIN, f64::max) - xs.fold(f64::MAX, f64::min)
+        };
+        for (flags, center, long) in [
+            ("0 0", (10.0, 0.0), false),
+            ("0 1", (0.0, 10.0), false),
+            ("1 0", (0.0, 10.0), true),
+            ("1 1", (10.0, 0.0), true),
+        ] {
+            let ring = single_ring(&format!("M0 0 A10 10 0 {} 10 10", flags));
+            assert!(on_circle(&ring, center, 10.0), "{}", flags);
+            assert_eq!(span(&ring) > 10.0 + 1e-9, long, "{}", flags);
+        }
+
+        // Rotating the axes of an ellipse.
+        let ring = single_ring("M0 0 A20 10 90 0 1 0 20");
+        assert!(
+            ring.iter()
+                .all(|&(x, y)| (x / 10.0).powi(2) + ((y - 10.0) / 20.0).powi(2) < 1.0 + 1e-9)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_path_data() {
+        for data in [
+            "L1 2",
+            "1 2",
+            "M0 0 X1 2",
+            "M0 0 L1",
+            "M0 0 A1 1 0 2 0 5 5",
+            "M0 0 L1 2 3",
+        ] {
+            assert!(
+                matches!(SvgPath::parse(data), Err(SvgError::Syntax(_))),
+                "{}",
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn reads_every_shape_element() {
+        let svg = Svg::parse(
+            r#"<?xml version="1.0"?>
+            <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 50">
+              <!-- <rect width="1000" height="1000"/> -->
+              <g fill="black">
+                <rect x="10" y="5" width="20" height="10px"/>
+                <circle cx="50" cy="25" r="5"/>
+                <ellipse cx='80' cy='25' rx='10' ry='4'/>
+                <polygon points="0,40 10,40 5,50"/>
+                <path d="M90 40 h10 v10 z"/>
+                <text x="0" y="0">ignored</text>
+              </g>
+            </svg>"#,
+        )
+        .unwrap();
+        assert_eq!(svg.paths.len(), 5);
+        let (min_x, min_y, max_x, max_y) = svg.bounds();
+        assert!(close((min_x, min_y), (0.0, 5.0)) && close((max_x, max_y), (100.0, 50.0)));
+
+        let shape = svg.shape(1.0, (0.0, 0.0));
+        for (x, y) in [
+            (20.0, 10.0),
+            (50.0, 25.0),
+            (88.0, 25.0),
+            (5.0, 42.0),
+            (99.0, 49.0),
+        ] {
+            assert!(shape.contains(x, y), "({}, {})", x, y);
+        }
+        for (x, y) in [(20.0, 20.0), (50.0, 31.0), (80.0, 30.0), (91.0, 49.0)] {
+            assert!(!shape.contains(x, y), "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn fit_scales_uniformly_and_centres() {
+        let svg = Svg::parse(r#"<rect x="-5" y="0" width="10" height="20"/>"#).unwrap();
+        // Height limits the scale to 2, leaving a 20 by 40 box centred in
+        // the 100 by 40 target.
+        let shape = svg.fit((0.0, 10.0), (100.0, 50.0));
+        assert!(shape.contains(40.5, 10.5) && shape.contains(59.5, 49.5));
+        assert!(!shape.contains(39.5, 30.0) && !shape.contains(60.5, 30.0));
+
+        for degenerate in ["<svg/>", r#"<rect x="3" y="4" width="0" height="0"/>"#] {
+            let svg = Svg::parse(degenerate).unwrap();
+            assert_eq!(svg.fit((0.0, 0.0), (10.0, 10.0)), Shape::union(Vec::new()));
+        }
+    }
+
+    #[test]
+    fn separate_subpaths_fill_only_themselves() {
+        let path = SvgPath::parse("M0 0h1v1h-1z M100 0h1v1h-1z M50 100h1v1h-1z").unwrap();
+        let shape = path.shape(1.0, (0.0, 0.0));
+        for (x, y) in [(0.5, 0.5), (100.5, 0.5), (50.5, 100.5)] {
+            assert!(shape.contains(x, y), "({}, {}) should be filled", x, y);
+        }
+        for (x, y) in [(50.0, 30.0), (50.0, 1.0), (25.0, 50.0), (75.0, 50.0)] {
+            assert!(!shape.contains(x, y), "({}, {}) should be empty", x, y);
+        }
+    }
+
+    #[test]
+    fn inner_rings_cut_holes() {
+        let path = SvgPath::parse("M0 0H10V10H0Z M3 3H7V7H3Z M20 0H30V10H20Z").unwrap();
+        let shape = path.shape(2.0, (1.0, 1.0));
+        assert!(shape.contains(4.0, 4.0));
+        assert!(!shape.contains(11.0, 11.0));
+        assert!(shape.contains(51.0, 11.0));
+        assert!(!shape.contains(31.0, 11.0));
+    }
+}
@@ -1,10 +1,9 @@
-#![allow(unused)]
 use std::{
     fmt::{Debug, Display},
     ops::{Index, IndexMut},
 };
 
-#[derive(Clone)]
+#[derive(Clone, Default, PartialEq)]
 pub struct Array2D<T> {
     pub data: Vec<T>,
     pub width: usize,
@@ -52,6 +51,31 @@ impl<T> Array2D<T> {
     }
 }
 
+/// Runs `f` on each `width`-long row of `data`, in parallel when the
+/// `parallel` feature is enabled. Rows must be written independently of each
+/// other, which keeps results identical regardless of thread count.
+#[cfg(feature = "parallel")]
+pub(crate) fn for_each_row<T, F>(data: &mut [T], width: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Sync + Send,
+{
+    use rayon::prelude::*;
+    data.par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+}
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn for_each_row<T, F>(data: &mut [T], width: usize, f: F)
+where
+    F: Fn(usize, &mut [T]),
+{
+    data.chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+}
+
 impl<T> Index<(usize, usize)> for Array2D<T> {
     type Output = T;
 
@@ -107,20 +131,99 @@ impl<T> Array2D<T>
 where
     T: Display + Copy,
 {
-    pub(crate) fn fill_circle(&mut self, center_x: i32, center_y: i32, radius: f32, value: T) {
+    pub fn fill_circle(&mut self, center_x: i32, center_y: i32, radius: f32, value: T) {
+        self.fill_circle_wrapping(center_x, center_y, radius, value, (None, None));
+    }
+
+    /// Like `fill_circle`, but along an axis with a period the circle wraps
+    /// around to the other side instead of being cut off. A staggered array
+    /// one longer than its period stores the wrapped face twice, so both
+    /// copies are written.
+    pub fn fill_circle_wrapping(
+        &mut self,
+        center_x: i32,
+        center_y: i32,
+        radius: f32,
+        value: T,
+        period: (Option<usize>, Option<usize>),
+    ) {
         let r_squared = (radius * radius) as i32;
 
         for y in (center_y - radius.ceil() as i32)..=(center_y + radius.ceil() as i32) {
             for x in (center_x - radius.ceil() as i32)..=(center_x + radius.ceil() as i32) {
-                if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
-                    let dx = x - center_x;
-                    let dy = y - center_y;
-
-                    if dx * dx + dy * dy <= r_squared {
-                        self[(x as usize, y as usize)] = value;
+                let dx = x - center_x;
+                let dy = y - center_y;
+                if dx * dx + dy * dy > r_squared {
+                    continue;
+                }
+                for xi in wrapped(x, self.width, period.0) {
+                    for yi in wrapped(y, self.height, period.1) {
+                        self[(xi, yi)] = value;
                     }
                 }
             }
         }
     }
 }
+
+/// Larger of `a` and `b`, or NaN if either is NaN. Unlike `f64::max` this
+/// keeps a blown-up field from reporting a finite maximum.
+pub(crate) fn max_or_nan(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else {
+        a.max(b)
+    }
+}
+
+/// The in-bounds indices along an axis of length `len` that `i` refers to.
+pub(crate) fn wrapped(i: i32, len: usize, period: Option<usize>) -> impl Iterator<Item = usize> {
+    let (first, second) = match period {
+        Some(period) => {
+            let i = i.rem_euclid(period as i32) as usize;
+            (Some(i), Some(i + period))
+        }
+        None => (usize::try_from(i).ok(), None),
+    };
+    [first, second]
+        .into_iter()
+        .flatten()
+        .filter(move |&i| i < len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Array2D, wrapped};
+
+    #[test]
+    fn wrapped_indices() {
+        let collect = |i, len, period| wrapped(i, len, period).collect::<Vec<_>>();
+        assert_eq!(collect(3, 10, None), [3]);
+        assert_eq!(collect(-1, 10, None), [] as [usize; 0]);
+        assert_eq!(collect(10, 10, None), [] as [usize; 0]);
+        assert_eq!(collect(-1, 10, Some(10)), [9]);
+        assert_eq!(collect(12, 10, Some(10)), [2]);
+        // A staggered axis stores its wrapped face at both ends.
+        assert_eq!(collect(0, 11, Some(10)), [0, 10]);
+        assert_eq!(collect(-10, 11, Some(10)), [0, 10]);
+        assert_eq!(collect(9, 11, Some(10)), [9]);
+    }
+
+    #[test]
+    fn circles_wrap_around_periodic_edges() {
+        let mut cut: Array2D<u8> = Array2D::new(8, 6);
+        cut.fill_circle(0, 0, 1.0, 1);
+        assert_eq!(cut.data.iter().filter(|&&v| v == 1).count(), 3);
+
+        let mut wrapped: Array2D<u8> = Array2D::new(8, 6);
+        wrapped.fill_circle_wrapping(0, 0, 1.0, 1, (Some(8), Some(6)));
+        for (x, y) in [(0, 0), (1, 0), (7, 0), (0, 1), (0, 5)] {
+            assert_eq!(wrapped[(x, y)], 1, "({}, {})", x, y);
+        }
+        assert_eq!(wrapped.data.iter().filter(|&&v| v == 1).count(), 5);
+
+        let mut faces: Array2D<u8> = Array2D::new(9, 6);
+        faces.fill_circle_wrapping(8, 3, 0.0, 1, (Some(8), None));
+        assert_eq!((faces[(0, 3)], faces[(8, 3)]), (1, 1));
+    }
+}
@@ -1,4 +1,7 @@
-use crate::sim::{EPSILON, Simulation};
+use crate::airfoil::{Airfoil, NacaProfile};
+use crate::config::SimConfig;
+use crate::shape::Shape;
+use crate::sim::{EPSILON, ScalarChannel, ScalarSource, Simulation};
 use hsv::{self, hsv_to_rgb};
 use pixels::{Pixels, SurfaceTexture};
 use std::cmp::min;
@@ -14,6 +17,13 @@ use winit::{
 pub struct Visualization {
     color_mode: ColorMode,
     vis_mode: VisualizationMode,
+    /// Channel shown in `Dyes` mode; `None` blends all of them.
+    channel: Option<usize>,
+    brush: Brush,
+    /// Whether dragging removes obstacles instead of adding them.
+    erasing: bool,
+    /// Airfoil that the bracket keys rotate, if one was placed.
+    airfoil: Option<Airfoil>,
     pixel_scale: u32,
     window: Window,
     pixels: Pixels,
@@ -35,13 +45,68 @@ enum VisualizationMode {
     Speed,
     SmokePressure,
     SmokeSpeed,
+    Curl,
+    Temperature,
+    Dyes,
+}
+
+/// Shape drawn under the cursor while the left mouse button is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Brush {
+    Circle,
+    Square,
+    Diamond,
+    Ellipse,
+    /// Thick line following the cursor between frames.
+    Stroke,
+}
+
+impl Brush {
+    fn next(self) -> Self {
+        match self {
+            Brush::Circle => Brush::Square,
+            Brush::Square => Brush::Diamond,
+            Brush::Diamond => Brush::Ellipse,
+            Brush::Ellipse => Brush::Stroke,
+            Brush::Stroke => Brush::Circle,
+        }
+    }
+
+    /// Shape of the brush at grid position `at`, having been at `previous`
+    /// on the last frame of the drag.
+    fn shape(self, at: (f64, f64), previous: Option<(f64, f64)>) -> Shape {
+        match self {
+            Brush::Circle => Shape::circle(at, 2.5),
+            Brush::Square => Shape::rotated_rectangle(at, (5.0, 5.0), 0.0),
+            Brush::Diamond => Shape::rotated_rectangle(at, (5.0, 5.0), std::f64::consts::FRAC_PI_4),
+            Brush::Ellipse => Shape::ellipse(at, (5.0, 2.0), 0.0),
+            Brush::Stroke => Shape::segment(previous.unwrap_or(at), at, 3.0),
+        }
+    }
 }
 
 const RECORDING_INTERVAL: u8 = 4;
+/// Angle of attack change per press of a bracket key, in degrees.
+const AIRFOIL_STEP_DEGREES: f64 = 1.0;
 const DEBUG_MODE: bool = false;
 
 impl Visualization {
-    pub fn new(width: u32, height: u32) -> Self {
+    /// Opens a window on a fresh simulation with the `default_dyes` added, so
+    /// the `Dyes` mode has something to show.
+    pub fn new(width: u32, height: u32, config: SimConfig) -> Self {
+        let mut sim = Simulation::new(width as usize, height as usize, config);
+        for channel in default_dyes(height as usize) {
+            sim.add_channel(channel)
+                .expect("default dyes have valid rates");
+        }
+        Self::with_simulation(sim)
+    }
+
+    /// Opens a window on an already configured simulation, e.g. one with
+    /// scalar channels or heat sources added.
+    pub fn with_simulation(sim: Simulation) -> Self {
+        let width = sim.width() as u32;
+        let height = sim.height() as u32;
         let pixel_scale = min(1864 / height, 2880 / width);
         let event_loop = EventLoop::new();
         let physical_size = PhysicalSize::new(width * pixel_scale, height * pixel_scale);
@@ -57,11 +122,14 @@ impl Visualization {
             SurfaceTexture::new(physical_size.width, physical_size.height, &window);
 
         let pixels = Pixels::new(width, height, surface_texture).unwrap();
-        let sim = Simulation::new(width as usize, height as usize);
 
         Visualization {
             color_mode: ColorMode::Color,
             vis_mode: VisualizationMode::SmokePressure,
+            channel: None,
+            brush: Brush::Circle,
+            erasing: false,
+            airfoil: None,
             pixel_scale,
             window,
             pixels,
@@ -70,43 +138,80 @@ impl Visualization {
         }
     }
 
+    /// Draws `airfoil` in place of the one set before, if any, and lets the
+    /// `[` and `]` keys adjust its angle of attack. Other obstacles stay.
+    pub fn with_airfoil(mut self, airfoil: Airfoil) -> Self {
+        place_airfoil(&mut self.sim, self.airfoil.as_ref(), &airfoil);
+        self.airfoil = Some(airfoil);
+        self
+    }
+
     pub fn run(mut self) {
         let mut cursor_position: Option<(f64, f64)> = None;
         let mut last_frame_start = Instant::now();
         let mut frame_time = Duration::ZERO;
         let mut ticker: u8 = 0;
         let mut mouse_down = false;
+        let mut last_brush_position: Option<(f64, f64)> = None;
         let mut recording = false;
 
         self.event_loop.run(move |event, _, control_flow| {
             control_flow.set_poll();
-            if ticker % 16 == 0 {
+            use ColorMode as cm;
+            use VisualizationMode as vm;
+            let dye = self.channel.and_then(|i| self.sim.channels().get(i));
+
+            if ticker.is_multiple_of(16) {
+                let mode = match (self.vis_mode, dye) {
+                    (vm::Dyes, Some(channel)) => format!("Dyes ({})", channel.name),
+                    (mode, _) => format!("{:?}", mode),
+                };
+                let airfoil = self.airfoil.map_or(String::new(), |airfoil| {
+                    format!(" - AoA {:.0} deg", airfoil.angle_of_attack.to_degrees())
+                });
                 self.window.set_title(&format!(
-                    "Eulerian Fluid Simulation: {} {:?} mode - {:?} - FPS: {:.0}",
+                    "Eulerian Fluid Simulation: {} {:?} mode - {} - {:?} {}{} - FPS: {:.0}",
                     if recording { "(RECORDING)" } else { "" },
                     self.color_mode,
-                    self.vis_mode,
-                    1.0 / frame_time.as_secs_f64() as f64
+                    mode,
+                    self.brush,
+                    if self.erasing { "eraser" } else { "brush" },
+                    airfoil,
+                    1.0 / frame_time.as_secs_f64()
                 ));
             }
             ticker = ticker.wrapping_add(1);
 
-            use ColorMode as cm;
-            use VisualizationMode as vm;
-            let imag_buffer = match self.vis_mode {
-                vm::Pressure | vm::SmokePressure => self.sim.get_pressure(),
-                vm::Speed | vm::SmokeSpeed => &self.sim.get_speed(),
-                vm::Smoke => self.sim.get_smoke(),
-            };
-            let mask = match self.vis_mode {
-                vm::Pressure | vm::Speed | vm::Smoke => self.sim.get_s(),
-                vm::SmokeSpeed | vm::SmokePressure => self.sim.get_smoke(),
-            };
-            render(self.pixels.frame_mut(), imag_buffer, mask, self.color_mode);
+            if self.vis_mode == vm::Dyes && dye.is_none() {
+                render_dyes(
+                    self.pixels.frame_mut(),
+                    self.sim.channels(),
+                    self.sim.get_s(),
+                );
+            } else {
+                let imag_buffer = match self.vis_mode {
+                    vm::Pressure | vm::SmokePressure => self.sim.get_pressure(),
+                    vm::Speed | vm::SmokeSpeed => &self.sim.get_speed(),
+                    vm::Smoke => self.sim.get_smoke(),
+                    vm::Curl => self.sim.get_curl(),
+                    vm::Temperature => self.sim.get_temperature(),
+                    vm::Dyes => dye.map_or(self.sim.get_smoke(), ScalarChannel::values),
+                };
+                let mask = match self.vis_mode {
+                    vm::Pressure
+                    | vm::Speed
+                    | vm::Smoke
+                    | vm::Curl
+                    | vm::Temperature
+                    | vm::Dyes => self.sim.get_s(),
+                    vm::SmokeSpeed | vm::SmokePressure => self.sim.get_smoke(),
+                };
+                render(self.pixels.frame_mut(), imag_buffer, mask, self.color_mode);
+            }
 
             _ = self.pixels.render();
 
-            if recording && (ticker % RECORDING_INTERVAL) == 0 {
+            if recording && ticker.is_multiple_of(RECORDING_INTERVAL) {
                 output_frame(
                     self.window.inner_size().width / self.pixel_scale,
                     self.window.inner_size().height / self.pixel_scale,
@@ -121,9 +226,15 @@ impl Visualization {
 
             if mouse_down {
                 let cursor_pos = cursor_position.unwrap();
-                let grid_x = (cursor_pos.0 / self.pixel_scale as f64) as i32;
-                let grid_y = (cursor_pos.1 / self.pixel_scale as f64) as i32;
-                self.sim.draw_obstacle(grid_x, grid_y, 2.5);
+                let scale = self.pixel_scale as f64;
+                let at = (cursor_pos.0 / scale, cursor_pos.1 / scale);
+                let shape = self.brush.shape(at, last_brush_position);
+                if self.erasing {
+                    self.sim.erase_shape(&shape);
+                } else {
+                    self.sim.draw_shape(&shape);
+                }
+                last_brush_position = Some(at);
             }
 
             use WindowEvent as we;
@@ -145,6 +256,7 @@ impl Visualization {
                         ..
                     } => {
                         mouse_down = false;
+                        last_brush_position = None;
                     }
 
                     we::MouseInput {
@@ -176,8 +288,39 @@ impl Visualization {
                                 }
                                 VirtualKeyCode::C => {
                                     self.sim.reset();
+                                    self.channel = None;
+                                    self.airfoil = None;
                                     ticker = 0;
                                 }
+                                VirtualKeyCode::B => {
+                                    self.brush = self.brush.next();
+                                    ticker = 0;
+                                }
+                                VirtualKeyCode::E => {
+                                    self.erasing = !self.erasing;
+                                    ticker = 0;
+                                }
+                                VirtualKeyCode::A => {
+                                    let airfoil = default_airfoil(&self.sim);
+                                    place_airfoil(&mut self.sim, self.airfoil.as_ref(), &airfoil);
+                                    self.airfoil = Some(airfoil);
+                                    ticker = 0;
+                                }
+                                VirtualKeyCode::LBracket | VirtualKeyCode::RBracket => {
+                                    if let Some(old) = self.airfoil {
+                                        let mut airfoil = old;
+                                        let step = AIRFOIL_STEP_DEGREES.to_radians();
+                                        airfoil.angle_of_attack +=
+                                            if key == VirtualKeyCode::RBracket {
+                                                step
+                                            } else {
+                                                -step
+                                            };
+                                        place_airfoil(&mut self.sim, Some(&old), &airfoil);
+                                        self.airfoil = Some(airfoil);
+                                        ticker = 0;
+                                    }
+                                }
                                 VirtualKeyCode::R => {
                                     recording = !recording;
                                     ticker = 0;
@@ -188,7 +331,10 @@ impl Visualization {
                                         vm::Smoke => vm::Speed,
                                         vm::Speed => vm::SmokePressure,
                                         vm::SmokePressure => vm::SmokeSpeed,
-                                        vm::SmokeSpeed => vm::Pressure,
+                                        vm::SmokeSpeed => vm::Curl,
+                                        vm::Curl => vm::Temperature,
+                                        vm::Temperature => vm::Dyes,
+                                        vm::Dyes => vm::Pressure,
                                     };
                                     ticker = 0;
                                 }
@@ -198,7 +344,19 @@ impl Visualization {
                                         vm::Speed => vm::Smoke,
                                         vm::SmokePressure => vm::Speed,
                                         vm::SmokeSpeed => vm::SmokePressure,
-                                        vm::Pressure => vm::SmokeSpeed,
+                                        vm::Curl => vm::SmokeSpeed,
+                                        vm::Temperature => vm::Curl,
+                                        vm::Dyes => vm::Temperature,
+                                        vm::Pressure => vm::Dyes,
+                                    };
+                                    ticker = 0;
+                                }
+                                VirtualKeyCode::Tab => {
+                                    let count = self.sim.channels().len();
+                                    self.channel = match self.channel {
+                                        None if count > 0 => Some(0),
+                                        Some(i) if i + 1 < count => Some(i + 1),
+                                        _ => None,
                                     };
                                     ticker = 0;
                                 }
@@ -229,6 +387,48 @@ impl Visualization {
     }
 }
 
+/// Erases the `previous` airfoil and draws `airfoil`, so it can be redrawn at
+/// a new angle without leaving its old outline behind or clearing the other
+/// obstacles.
+fn place_airfoil(sim: &mut Simulation, previous: Option<&Airfoil>, airfoil: &Airfoil) {
+    if let Some(previous) = previous {
+        sim.erase_shape(&previous.shape());
+    }
+    sim.draw_shape(&airfoil.shape());
+}
+
+/// A red, a green and a blue streak entering through the inflow edge at a
+/// quarter, half and three quarters of the height, the outer two fading as
+/// they travel.
+fn default_dyes(height: usize) -> Vec<ScalarChannel> {
+    let streak = |center: usize| ScalarSource::Inflow {
+        start: center.saturating_sub(height / 40),
+        end: center + height / 40 + 1,
+        value: 1.0,
+    };
+    vec![
+        ScalarChannel::new("red", [255, 64, 64])
+            .with_source(streak(height / 4))
+            .with_decay(0.2),
+        ScalarChannel::new("green", [64, 255, 64]).with_source(streak(height / 2)),
+        ScalarChannel::new("blue", [64, 128, 255])
+            .with_source(streak(3 * height / 4))
+            .with_decay(0.2),
+    ]
+}
+
+/// A NACA 2412 section a third of the way into the domain.
+fn default_airfoil(sim: &Simulation) -> Airfoil {
+    let (width, height) = (sim.width() as f64, sim.height() as f64);
+    let profile = NacaProfile::FourDigit {
+        camber: 0.02,
+        camber_position: 0.4,
+        thickness: 0.12,
+    };
+    Airfoil::new(profile, width / 3.0, (width / 3.0, height / 2.0))
+        .with_angle_of_attack(5f64.to_radians())
+}
+
 fn render(frame: &mut [u8], imag: &[f64], mask: &[f64], cm: ColorMode) {
     let min = imag.iter().fold(f64::MAX, |acc, &x| acc.min(x));
     let max = imag.iter().fold(f64::MIN, |acc, &x| acc.max(x));
@@ -261,12 +461,30 @@ fn render(frame: &mut [u8], imag: &[f64], mask: &[f64], cm: ColorMode) {
                 }
             }
         })
-        .map(|(r, g, b)| [r as u8, g as u8, b as u8, 255])
-        .flatten()
+        .flat_map(|(r, g, b)| [r, g, b, 255])
         .collect();
     frame.copy_from_slice(&buffer);
 }
 
+/// Draws every channel in its own color, adding where they overlap, with
+/// obstacles left black.
+fn render_dyes(frame: &mut [u8], channels: &[ScalarChannel], mask: &[f64]) {
+    for (i, (pixel, &m)) in frame.chunks_exact_mut(4).zip(mask).enumerate() {
+        let mut rgb = [0.0f64; 3];
+        for channel in channels {
+            let c = channel.values()[i].clamp(0.0, 1.0);
+            for (sum, &component) in rgb.iter_mut().zip(&channel.color) {
+                *sum += c * component as f64;
+            }
+        }
+        let m = m.clamp(0.0, 1.0);
+        for (out, sum) in pixel.iter_mut().zip(rgb) {
+            *out = (sum.min(255.0) * m) as u8;
+        }
+        pixel[3] = 255;
+    }
+}
+
 fn output_frame(width: u32, height: u32, pixel_data: &[u8]) {
     use std::io::{self, BufWriter, Write};
 